@@ -1,8 +1,12 @@
+use std::f32::consts::{FRAC_PI_2, PI, TAU};
+
 use rayon::iter::ParallelIterator;
 
 use crate::primitives::{Color, rand};
+use crate::primitives::transform::Transform;
 
-use super::scene::get_ray_color;
+use super::scene::{get_debug_color, get_ray_color};
+use super::settings::{RenderSettings, View};
 
 use super::{
     canvas::Canvas,
@@ -10,61 +14,421 @@ use super::{
     Ray, Scene,
 };
 
-#[derive(Debug)]
+const WORLD_UP: Vec3d = Vec3d { x: 0., y: 0., z: 1. };
+const MIN_FOV: f32 = 1.;
+const MAX_FOV: f32 = 170.;
+
+/// Which image dimension the field of view is measured along.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum SensorFit {
+    /// Along the larger dimension of the image.
+    Auto,
+    Horizontal,
+    Vertical,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Projection {
+    Perspective,
+    /// Parallel rays, the view size is set by `Camera::ortho_scale`.
+    Orthographic,
+    /// 360° latitude-longitude panorama centered on the viewing direction.
+    Equirectangular,
+}
+
+/// Shape of the lens opening, which is also the shape of out-of-focus highlights.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ApertureShape {
+    Circular,
+    Polygonal { blades: u32 },
+}
+
+impl ApertureShape {
+    /// Uniformly distributed point inside the aperture of radius 1.
+    fn sample(&self) -> (f32, f32) {
+        match *self {
+            ApertureShape::Circular => rand::get_random_in_unit_disk(),
+            ApertureShape::Polygonal { blades } => {
+                // pick one of the triangles between the center and an edge,
+                // then a uniform point inside of it
+                let blades = u32::max(blades, 3);
+                let blade = (rand::get_random_float() * blades as f32) as u32 % blades;
+                let angle = TAU / blades as f32;
+                let (sin_a, cos_a) = (angle * blade as f32).sin_cos();
+                let (sin_b, cos_b) = (angle * (blade + 1) as f32).sin_cos();
+                let mut u = rand::get_random_float();
+                let mut v = rand::get_random_float();
+                if u + v > 1. {
+                    u = 1. - u;
+                    v = 1. - v;
+                }
+                (u * cos_a + v * cos_b, u * sin_a + v * sin_b)
+            }
+        }
+    }
+}
+
+/// Camera with an orthonormal basis. `forward`, `left` and `up` always
+/// stay unit length and perpendicular to each other.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Camera {
     pub location: Point3d,
-    pub direction: Vec3d,
+    forward: Vec3d,
+    left: Vec3d,
+    up: Vec3d,
+    pub projection: Projection,
+    /// Field of view in degrees.
+    pub fov: f32,
+    /// Size of the orthographic view in world units.
+    pub ortho_scale: f32,
+    pub sensor_fit: SensorFit,
+    /// Radius of the lens. Zero gives a pinhole camera with everything in focus.
+    pub aperture_radius: f32,
+    pub aperture_shape: ApertureShape,
+    /// Distance along the viewing direction to the plane in perfect focus.
+    pub focus_distance: f32,
 }
 
 impl Camera {
-    pub fn render(&self, canvas: &mut Canvas, scene: &Scene) {
+    /// Looks from `eye` at `target`. If `up` is parallel to the viewing
+    /// direction, the world up axis or, failing that, the X axis is used instead.
+    pub fn look_at(eye: Point3d, target: Point3d, up: Vec3d) -> Camera {
+        let transform = [up, WORLD_UP, Vec3d::new(1., 0., 0.)]
+            .into_iter()
+            .find_map(|up| Transform::look_at(eye, target, up))
+            .expect("Camera target must differ from its location!");
+        Camera {
+            location: eye,
+            forward: transform.apply_vector(Vec3d::new(1., 0., 0.)),
+            left: transform.apply_vector(Vec3d::new(0., 1., 0.)),
+            up: transform.apply_vector(Vec3d::new(0., 0., 1.)),
+            projection: Projection::Perspective,
+            fov: 60.,
+            ortho_scale: 10.,
+            sensor_fit: SensorFit::Auto,
+            aperture_radius: 0.,
+            aperture_shape: ApertureShape::Circular,
+            focus_distance: (target - eye).len(),
+        }
+    }
+
+    /// Half extents of the image plane at distance 1 from the camera.
+    pub fn image_plane(&self, width: usize, height: usize) -> (f32, f32) {
+        self.fit((self.fov.to_radians() / 2.).tan(), width, height)
+    }
+
+    /// Half extents of the orthographic view in world units.
+    pub fn ortho_view(&self, width: usize, height: usize) -> (f32, f32) {
+        self.fit(self.ortho_scale / 2., width, height)
+    }
+
+    // spreads a half size along the fitted dimension over both of them
+    fn fit(&self, half_size: f32, width: usize, height: usize) -> (f32, f32) {
+        let ratio = width as f32 / height as f32;
+        let horizontal = match self.sensor_fit {
+            SensorFit::Auto => width >= height,
+            SensorFit::Horizontal => true,
+            SensorFit::Vertical => false,
+        };
+        if horizontal {
+            (half_size, half_size / ratio)
+        } else {
+            (half_size * ratio, half_size)
+        }
+    }
+
+    /// Direction of the pinhole ray through a point on the image, given in pixels.
+    /// Its component along the viewing direction is always 1.
+    pub fn pixel_direction(&self, x: f32, y: f32, width: usize, height: usize) -> Vec3d {
+        let (half_width, half_height) = self.image_plane(width, height);
+        let (horizontal, vertical) = image_coordinates(x, y, width, height);
+        self.forward + self.left * (half_width * horizontal) + self.up * (half_height * vertical)
+    }
+
+    /// Ray through a point on the image, given in pixels, ignoring the lens.
+    pub fn get_pinhole_ray(&self, x: f32, y: f32, width: usize, height: usize) -> Ray {
+        match self.projection {
+            Projection::Perspective => Ray {
+                origin: self.location,
+                direction: self.pixel_direction(x, y, width, height),
+            },
+            Projection::Orthographic => {
+                let (half_width, half_height) = self.ortho_view(width, height);
+                let (horizontal, vertical) = image_coordinates(x, y, width, height);
+                Ray {
+                    origin: self.location
+                        + self.left * (half_width * horizontal)
+                        + self.up * (half_height * vertical),
+                    direction: self.forward,
+                }
+            }
+            Projection::Equirectangular => {
+                let (horizontal, vertical) = image_coordinates(x, y, width, height);
+                let (sin_lon, cos_lon) = (horizontal * PI).sin_cos();
+                let (sin_lat, cos_lat) = (vertical * FRAC_PI_2).sin_cos();
+                Ray {
+                    origin: self.location,
+                    direction: (self.forward * cos_lon + self.left * sin_lon) * cos_lat + self.up * sin_lat,
+                }
+            }
+        }
+    }
+
+    /// Thin lens model: the ray starts at a random point on the aperture and
+    /// passes through the point the pinhole ray hits on the focus plane.
+    /// Only the perspective projection has a lens.
+    pub fn get_ray(&self, x: f32, y: f32, width: usize, height: usize) -> Ray {
+        if self.projection != Projection::Perspective || self.aperture_radius <= 0. {
+            return self.get_pinhole_ray(x, y, width, height)
+        }
+        let direction = self.pixel_direction(x, y, width, height);
+        let focus_point = self.location + direction * self.focus_distance;
+        let (lens_x, lens_y) = self.aperture_shape.sample();
+        let origin = self.location
+            + self.left * (lens_x * self.aperture_radius)
+            + self.up * (lens_y * self.aperture_radius);
+        Ray { origin, direction: focus_point - origin }
+    }
+
+    /// Narrows the field of view by `factor`, values below 1 zoom out.
+    pub fn zoom(&mut self, factor: f32) {
+        if self.projection == Projection::Orthographic {
+            self.ortho_scale /= factor;
+            return
+        }
+        let half_fov = (self.fov.to_radians() / 2.).tan() / factor;
+        self.fov = (2. * half_fov.atan()).to_degrees().clamp(MIN_FOV, MAX_FOV);
+    }
+
+    pub fn render(&self, canvas: &mut Canvas, scene: &Scene, settings: &RenderSettings) {
         let width = canvas.width;
         let height = canvas.height;
-        let ratio = width as f32 / height as f32;
-        let left = self.get_left().normalize() * ratio;
-        let top = self.direction.cross(&left).normalize();
-        let top_left = self.direction + top + left;
-        let step_v = 2. / (width - 1) as f32;
-        let step_h = 2. / (height - 1) as f32;
-        let half_step_v = step_v / 2.;
-        let half_step_h = step_h / 2.;
+        let samples = settings.samples_per_pixel;
 
         canvas.par_rows_mut().for_each(|mut row| {
             let y = row.y;
-            let current_v = top_left - (top * (step_h * (y) as f32));
             for x in 0..width {
                 let mut color = Color::new(0., 0., 0.);
-                let factor = 1;
-                for _ in 0..factor {
-                    let h_deviation = rand::get_random_float_neg_pos(half_step_h);
-                    let v_deviation = rand::get_random_float_neg_pos(half_step_v);
-                    let direction = current_v - left * (step_v * (x) as f32 + v_deviation) + top * h_deviation;
-                    let ray = Ray {
-                        origin: self.location,
-                        direction,
+                for _ in 0..samples {
+                    let jittered_x = x as f32 + rand::get_random_float_neg_pos(0.5);
+                    let jittered_y = y as f32 + rand::get_random_float_neg_pos(0.5);
+                    let ray = self.get_ray(jittered_x, jittered_y, width, height);
+                    let ray_color = match settings.view {
+                        View::Shaded => get_ray_color(&ray, scene, settings.max_depth),
+                        view => get_debug_color(&ray, scene, view),
                     };
-                    let ray_color = get_ray_color(&ray, &scene, 2);
-                    color = color + ray_color * (1. / factor as f32)
+                    color = color + ray_color * (1. / samples as f32)
                 }
                 row.draw_pixel(x, color);
             }
         })
     }
 
-    pub fn rotate_x(&mut self, theta: f32) {
-        self.direction = self.direction.rotate_x(theta)
+    /// Turns left around the world up axis, so the horizon stays level.
+    pub fn yaw(&mut self, theta: f32) {
+        self.rotate(Transform::rotate(WORLD_UP, theta));
+    }
+
+    /// Tilts up around the camera's own left axis.
+    pub fn pitch(&mut self, theta: f32) {
+        self.rotate(Transform::rotate(self.left, -theta));
+    }
+
+    /// Rolls clockwise around the viewing direction.
+    pub fn roll(&mut self, theta: f32) {
+        self.rotate(Transform::rotate(self.forward, theta));
     }
 
     pub fn move_forward(&mut self, step: f32) {
-        self.location = self.location + self.direction.normalize() * step;
+        self.location = self.location + self.forward * step;
     }
 
     pub fn move_left(&mut self, step: f32) {
-        self.location = self.location + self.get_left().normalize() * step;
+        self.location = self.location + self.left * step;
+    }
+
+    pub fn move_up(&mut self, step: f32) {
+        self.location = self.location + self.up * step;
     }
 
-    fn get_left(&self) -> Vec3d {
-        let top = Vec3d::new(0., 0., 1.);
-        top.cross(&self.direction)
+    fn rotate(&mut self, rotation: Transform) {
+        // re-orthonormalize so rounding errors don't pile up over many frames
+        let forward = rotation.apply_vector(self.forward).normalize();
+        let up = rotation.apply_vector(self.up);
+        let left = up.cross(&forward).normalize();
+        self.forward = forward;
+        self.left = left;
+        self.up = forward.cross(&left);
+    }
+}
+
+/// Maps pixel coordinates to [-1, 1], with 1 at the left and top edges.
+fn image_coordinates(x: f32, y: f32, width: usize, height: usize) -> (f32, f32) {
+    let horizontal = 1. - 2. * x / (width - 1) as f32;
+    let vertical = 1. - 2. * y / (height - 1) as f32;
+    (horizontal, vertical)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::vec3d::assert_close;
+
+    fn camera() -> Camera {
+        Camera::look_at(Point3d::new(0., 0., 0.), Point3d::new(1., 0., 0.), WORLD_UP)
+    }
+
+    fn assert_orthonormal(camera: &Camera) {
+        for axis in [camera.forward, camera.left, camera.up] {
+            assert!((axis.len() - 1.).abs() < 1e-5);
+        }
+        assert_close(camera.forward.cross(&camera.left), camera.up);
+        assert_close(camera.left.cross(&camera.up), camera.forward);
+    }
+
+    #[test]
+    fn rotations_keep_the_basis_orthonormal() {
+        let mut camera = camera();
+        for _ in 0..1000 {
+            camera.yaw(0.3);
+            camera.pitch(0.2);
+            camera.roll(-0.1);
+        }
+
+        assert_orthonormal(&camera);
+    }
+
+    #[test]
+    fn yaw_pitch_and_roll() {
+        let mut camera = camera();
+        camera.yaw(FRAC_PI_2);
+        assert_close(camera.forward, Vec3d::new(0., 1., 0.));
+        assert_close(camera.up, WORLD_UP);
+
+        camera.pitch(FRAC_PI_2);
+        assert_close(camera.forward, WORLD_UP);
+        assert_close(camera.up, Vec3d::new(0., -1., 0.));
+
+        let left = camera.left;
+        camera.roll(FRAC_PI_2);
+        assert_close(camera.forward, WORLD_UP);
+        assert_close(camera.up, left * -1.);
+    }
+
+    #[test]
+    fn looking_straight_up_or_down() {
+        for target_z in [5., -5.] {
+            let camera = Camera::look_at(Point3d::new(0., 0., 0.), Point3d::new(0., 0., target_z), WORLD_UP);
+
+            assert_close(camera.forward, Vec3d::new(0., 0., target_z / 5.));
+            assert_orthonormal(&camera);
+        }
+    }
+
+    #[test]
+    fn look_at_with_parallel_up_falls_back() {
+        let camera = Camera::look_at(Point3d::new(0., 0., 0.), Point3d::new(3., 0., 0.), Vec3d::new(-2., 0., 0.));
+
+        assert_close(camera.forward, Vec3d::new(1., 0., 0.));
+        assert_close(camera.up, WORLD_UP);
+        assert_orthonormal(&camera);
+    }
+    fn assert_extent((width, height): (f32, f32), expected: (f32, f32)) {
+        assert!((width - expected.0).abs() < 1e-5 && (height - expected.1).abs() < 1e-5, "{:?}", (width, height));
+    }
+
+    #[test]
+    fn field_of_view_along_the_fitted_dimension() {
+        let mut camera = camera();
+        camera.fov = 90.;
+
+        assert_extent(camera.image_plane(200, 100), (1., 0.5));
+        assert_extent(camera.image_plane(100, 200), (0.5, 1.));
+        camera.sensor_fit = SensorFit::Horizontal;
+        assert_extent(camera.image_plane(100, 200), (1., 2.));
+        camera.sensor_fit = SensorFit::Vertical;
+        assert_extent(camera.image_plane(200, 100), (2., 1.));
+
+        camera.ortho_scale = 4.;
+        assert_extent(camera.ortho_view(200, 100), (4., 2.));
+    }
+
+    #[test]
+    fn zoom_is_clamped() {
+        let mut camera = camera();
+        camera.zoom(1e3);
+        assert!(camera.fov == MIN_FOV);
+        camera.zoom(1e-5);
+        assert!(camera.fov == MAX_FOV);
+
+        camera.fov = 90.;
+        camera.zoom(2.);
+        assert_extent(camera.image_plane(100, 100), (0.5, 0.5));
+    }
+    #[test]
+    fn lens_rays_converge_on_the_focus_plane() {
+        let mut camera = Camera::look_at(Point3d::new(1., 2., 3.), Point3d::new(4., 6., 3.), WORLD_UP);
+        camera.aperture_radius = 0.5;
+        camera.aperture_shape = ApertureShape::Polygonal { blades: 6 };
+        camera.focus_distance = 7.;
+        let focus_point = camera.get_pinhole_ray(10., 20., 64, 48);
+        let focus_point = focus_point.origin + focus_point.direction * camera.focus_distance;
+
+        assert!(((focus_point - camera.location).dot(&camera.forward) - 7.).abs() < 1e-4);
+        for _ in 0..100 {
+            let ray = camera.get_ray(10., 20., 64, 48);
+            assert!((ray.origin - camera.location).dot(&camera.forward).abs() < 1e-5);
+            assert!((ray.origin - camera.location).len() <= 0.5 + 1e-5);
+            assert!((ray.origin + ray.direction - focus_point).len() < 1e-4);
+        }
+    }
+
+    #[test]
+    fn polygonal_samples_stay_inside_the_polygon() {
+        for blades in [3, 5, 8] {
+            let shape = ApertureShape::Polygonal { blades };
+            let edge_angle = TAU / blades as f32;
+            // the distance of every edge from the center
+            let apothem = (edge_angle / 2.).cos();
+            for _ in 0..10_000 {
+                let (x, y) = shape.sample();
+                for edge in 0..blades {
+                    let (sin, cos) = (edge_angle * (edge as f32 + 0.5)).sin_cos();
+                    assert!(x * cos + y * sin <= apothem + 1e-5, "{:?} is outside {} blades", (x, y), blades);
+                }
+            }
+        }
+    }
+    #[test]
+    fn orthographic_rays_are_parallel() {
+        let mut camera = camera();
+        camera.projection = Projection::Orthographic;
+        camera.ortho_scale = 4.;
+
+        let pixels = [
+            (0., 0., Point3d::new(0., 2., 1.2)),
+            (2., 1., Point3d::new(0., 0., 0.)),
+            (4., 2., Point3d::new(0., -2., -1.2)),
+        ];
+        for (x, y, origin) in pixels {
+            let ray = camera.get_pinhole_ray(x, y, 5, 3);
+            assert_close(ray.origin, origin);
+            assert_close(ray.direction, Vec3d::new(1., 0., 0.));
+        }
+    }
+
+    #[test]
+    fn equirectangular_covers_the_sphere() {
+        let mut camera = camera();
+        camera.projection = Projection::Equirectangular;
+        let direction = |x, y| camera.get_pinhole_ray(x, y, 5, 3).direction;
+
+        assert_close(direction(2., 1.), Vec3d::new(1., 0., 0.));
+        assert_close(direction(1., 1.), Vec3d::new(0., 1., 0.));
+        assert_close(direction(0., 1.), Vec3d::new(-1., 0., 0.));
+        assert_close(direction(4., 1.), Vec3d::new(-1., 0., 0.));
+        assert_close(direction(2., 0.), WORLD_UP);
+        assert_close(direction(0., 0.), WORLD_UP);
+        assert_close(direction(2., 2.), Vec3d::new(0., 0., -1.));
     }
 }
@@ -1,41 +1,121 @@
 use rayon::{iter::ParallelIterator, prelude::{IndexedParallelIterator, ParallelSliceMut}};
-use super::primitives::Color;
+use super::primitives::{Color, Color8b};
+use super::tonemap::ToneMapper;
 
 pub struct Canvas {
     pub(super) width: usize,
     pub(super) height: usize,
+    /// Tone mapped pixels, ready to be shown.
     pub(super) buffer: Vec<u32>,
+    // linear radiance summed over all frames rendered since the last reset
+    accumulation: Vec<Color>,
+    frames: u32,
+    pub tone_mapper: ToneMapper,
 }
 
 pub struct Row<'a> {
     pub(super) y: usize,
     pub(super) buffer: &'a mut [u32],
+    accumulation: &'a mut [Color],
+    weight: f32,
+    tone_mapper: ToneMapper,
 }
 
 impl Row<'_> {
+    /// Adds a sample to the pixel and shows the average of all samples so far.
     pub fn draw_pixel(&mut self, x: usize, color: Color) {
-        self.buffer[x] = color.into()
+        self.accumulation[x] = self.accumulation[x] + color;
+        self.buffer[x] = self.tone_mapper.apply(self.accumulation[x] * self.weight).into()
     }
 }
 
 impl Canvas {
     pub fn new(width: usize, height: usize) -> Self {
         let buffer = vec![0_u32; width * height];
+        let accumulation = vec![Color::new(0., 0., 0.); width * height];
         Self {
             height,
             width,
             buffer,
+            accumulation,
+            frames: 0,
+            tone_mapper: ToneMapper::default(),
         }
     }
 
-    pub fn clear(&mut self) {
-        self.buffer.iter_mut().for_each(|v| *v = 0);
+    /// Number of frames averaged in the current image.
+    pub fn frames(&self) -> u32 {
+        self.frames
     }
 
-    pub fn par_rows_mut(&mut self) -> impl IndexedParallelIterator<Item = Row> {
+    /// Averaged linear radiance of every pixel, row by row from the top.
+    pub fn radiance(&self) -> Vec<Color> {
+        let weight = 1. / u32::max(self.frames, 1) as f32;
+        self.accumulation.iter().map(|&color| color * weight).collect()
+    }
+
+    /// Tone mapped pixels, row by row from the top.
+    pub fn develop(&self) -> Vec<Color8b> {
+        self.radiance().into_iter().map(|color| self.tone_mapper.apply(color).into()).collect()
+    }
+
+    /// Starts accumulating from scratch. The displayed buffer is kept
+    /// until the next frame overwrites it.
+    pub fn reset(&mut self) {
+        self.accumulation.iter_mut().for_each(|v| *v = Color::new(0., 0., 0.));
+        self.frames = 0;
+    }
+
+    /// Every row of the next frame. Each pixel has to be drawn exactly once.
+    pub fn par_rows_mut(&mut self) -> impl IndexedParallelIterator<Item = Row<'_>> {
+        self.frames += 1;
+        let weight = 1. / self.frames as f32;
+        let tone_mapper = self.tone_mapper;
         self.buffer
             .par_chunks_mut(self.width)
+            .zip(self.accumulation.par_chunks_mut(self.width))
             .enumerate()
-            .map(move |(y, buffer)| { Row {y, buffer}})
+            .map(move |(y, (buffer, accumulation))| { Row {y, buffer, accumulation, weight, tone_mapper}})
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn draw_frame(canvas: &mut Canvas, color: Color) {
+        canvas.par_rows_mut().for_each(|mut row| {
+            for x in 0..row.buffer.len() {
+                row.draw_pixel(x, color);
+            }
+        });
+    }
+
+    #[test]
+    fn frames_are_averaged() {
+        let mut canvas = Canvas::new(3, 2);
+        draw_frame(&mut canvas, Color::new(1., 0., 0.5));
+        draw_frame(&mut canvas, Color::new(0., 0., 0.5));
+
+        assert!(canvas.frames() == 2);
+        let radiance = canvas.radiance();
+        assert!(radiance.len() == 6);
+        assert!(radiance.iter().all(|color| color.red == 0.5 && color.blue == 0.5));
+        // the displayed pixels show the average as well
+        let expected: u32 = canvas.tone_mapper.apply(Color::new(0.5, 0., 0.5)).into();
+        assert!(canvas.buffer.iter().all(|&pixel| pixel == expected));
+    }
+
+    #[test]
+    fn reset_starts_over() {
+        let mut canvas = Canvas::new(2, 2);
+        draw_frame(&mut canvas, Color::new(1., 1., 1.));
+        canvas.reset();
+
+        assert!(canvas.frames() == 0);
+        assert!(canvas.radiance().iter().all(|color| color.red == 0.));
+
+        draw_frame(&mut canvas, Color::new(0.25, 0.25, 0.25));
+        assert!(canvas.radiance().iter().all(|color| color.green == 0.25));
     }
 }
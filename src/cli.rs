@@ -0,0 +1,160 @@
+use std::path::PathBuf;
+
+use crate::settings::RenderSettings;
+
+pub const USAGE: &str = "\
+Usage:
+    raytracer [SCENE]
+        Opens the interactive window.
+    raytracer render [SCENE] --output FILE [OPTIONS]
+        Renders without a window and saves the image. The format follows
+        the extension of FILE: png, ppm, pfm or exr.
+
+Options:
+    -o, --output FILE   Where to save the image
+    -w, --width N       Image width in pixels (default 800)
+    -H, --height N      Image height in pixels (default 600)
+    -s, --spp N         Samples per pixel and pass, overrides the scene
+    -p, --passes N      Passes accumulated into the image (default 1)
+    -d, --depth N       Max ray depth, overrides the scene
+    -t, --threads N     Worker threads (default: one per core)
+    -h, --help          Prints this message";
+
+#[derive(Debug, PartialEq)]
+pub enum Command {
+    Window { scene: Option<PathBuf> },
+    Render(RenderOptions),
+    Help,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct RenderOptions {
+    pub scene: Option<PathBuf>,
+    pub output: PathBuf,
+    pub width: usize,
+    pub height: usize,
+    pub samples_per_pixel: Option<u32>,
+    pub max_depth: Option<u8>,
+    pub passes: u32,
+    pub threads: Option<usize>,
+}
+
+pub fn parse(args: impl IntoIterator<Item = String>) -> Result<Command, String> {
+    let mut args = args.into_iter().peekable();
+    if args.peek().map(String::as_str) != Some("render") {
+        let mut scene = None;
+        for arg in args {
+            match arg.as_str() {
+                "-h" | "--help" => return Ok(Command::Help),
+                _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+                _ if scene.is_none() => scene = Some(PathBuf::from(arg)),
+                _ => return Err(format!("Unexpected argument {arg}")),
+            }
+        }
+        return Ok(Command::Window { scene })
+    }
+    args.next();
+
+    let mut scene = None;
+    let mut output = None;
+    let mut width = 800;
+    let mut height = 600;
+    let mut samples_per_pixel = None;
+    let mut max_depth = None;
+    let mut passes = 1;
+    let mut threads = None;
+    while let Some(arg) = args.next() {
+        let mut value = || args.next().ok_or(format!("Missing value of {arg}"));
+        match arg.as_str() {
+            "-h" | "--help" => return Ok(Command::Help),
+            "-o" | "--output" => output = Some(PathBuf::from(value()?)),
+            "-w" | "--width" => width = parse_number(&arg, &value()?)?,
+            "-H" | "--height" => height = parse_number(&arg, &value()?)?,
+            "-s" | "--spp" => samples_per_pixel = Some(parse_number(&arg, &value()?)?),
+            "-p" | "--passes" => passes = parse_number(&arg, &value()?)?,
+            "-d" | "--depth" => max_depth = Some(parse_number(&arg, &value()?)?),
+            "-t" | "--threads" => threads = Some(parse_number(&arg, &value()?)?),
+            _ if arg.starts_with('-') => return Err(format!("Unknown option {arg}")),
+            _ if scene.is_none() => scene = Some(PathBuf::from(arg)),
+            _ => return Err(format!("Unexpected argument {arg}")),
+        }
+    }
+
+    let output = output.ok_or("Missing --output")?;
+    if width < 2 || height < 2 {
+        return Err("The image has to be at least 2x2 pixels".to_string())
+    }
+    if passes == 0 || threads == Some(0) {
+        return Err("Passes and threads have to be positive".to_string())
+    }
+    if let Some(samples_per_pixel) = samples_per_pixel
+        && !(1..=RenderSettings::MAX_SAMPLES_PER_PIXEL).contains(&samples_per_pixel)
+    {
+        return Err(format!("Samples per pixel have to be between 1 and {}", RenderSettings::MAX_SAMPLES_PER_PIXEL))
+    }
+    if let Some(max_depth) = max_depth
+        && max_depth > RenderSettings::MAX_DEPTH
+    {
+        return Err(format!("Max depth can't exceed {}", RenderSettings::MAX_DEPTH))
+    }
+    Ok(Command::Render(RenderOptions { scene, output, width, height, samples_per_pixel, max_depth, passes, threads }))
+}
+
+fn parse_number<T: std::str::FromStr>(option: &str, value: &str) -> Result<T, String> {
+    value.parse().map_err(|_| format!("Invalid value {value} of {option}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+
+    fn args(line: &str) -> Vec<String> {
+        line.split_whitespace().map(String::from).collect()
+    }
+
+    #[test]
+    fn window_by_default() {
+        assert!(parse(args("")) == Ok(Command::Window { scene: None }));
+        assert!(parse(args("a.scene")) == Ok(Command::Window { scene: Some(PathBuf::from("a.scene")) }));
+    }
+
+    #[test]
+    fn help() {
+        assert!(parse(args("-h")) == Ok(Command::Help));
+        assert!(parse(args("render a.scene -o out.png -h")) == Ok(Command::Help));
+    }
+
+    #[test]
+    fn render_options() {
+        let command = parse(args("render a.scene -o out.png --width 64 -H 32 --spp 4 -p 3 -d 5 -t 2")).unwrap();
+
+        let Command::Render(options) = command else { panic!() };
+        assert!(options.scene == Some(PathBuf::from("a.scene")));
+        assert!(options.output == Path::new("out.png"));
+        assert!(options.width == 64 && options.height == 32);
+        assert!(options.samples_per_pixel == Some(4) && options.max_depth == Some(5));
+        assert!(options.passes == 3);
+        assert!(options.threads == Some(2));
+    }
+
+    #[test]
+    fn render_errors() {
+        assert!(parse(args("render a.scene")).is_err());
+        assert!(parse(args("render -o out.png --width")).is_err());
+        assert!(parse(args("render -o out.png --spp many")).is_err());
+        assert!(parse(args("render -o out.png --fast")).is_err());
+        assert!(parse(args("render -o out.png --width 1")).is_err());
+    }
+
+    #[test]
+    fn render_limits() {
+        let max_samples = RenderSettings::MAX_SAMPLES_PER_PIXEL;
+        let max_depth = RenderSettings::MAX_DEPTH;
+        assert!(parse(args(&format!("render -o out.png --spp {max_samples} --depth {max_depth}"))).is_ok());
+        assert!(parse(args("render -o out.png --spp 0")).is_err());
+        assert!(parse(args(&format!("render -o out.png --spp {}", max_samples + 1))).is_err());
+        assert!(parse(args(&format!("render -o out.png --depth {}", max_depth + 1))).is_err());
+        assert!(parse(args("render -o out.png --passes 0")).is_err());
+    }
+}
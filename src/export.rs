@@ -0,0 +1,201 @@
+use std::fs::File;
+use std::io::{self, BufWriter, Write};
+use std::path::Path;
+
+use crate::canvas::Canvas;
+use crate::primitives::Color;
+
+pub const FORMATS: [&str; 4] = ["png", "ppm", "pfm", "exr"];
+
+/// Saves the canvas in the format given by the file extension:
+/// `png` and `ppm` are tone mapped 8-bit images, `pfm` and `exr` keep the
+/// linear HDR radiance.
+pub fn save(canvas: &Canvas, path: &Path) -> io::Result<()> {
+    let Some(extension) = format_of(path) else {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!("Unsupported image format of {}, use png, ppm, pfm or exr.", path.display()),
+        ))
+    };
+    let mut writer = BufWriter::new(File::create(path)?);
+    match extension.as_str() {
+        "png" => write_png(canvas, &mut writer)?,
+        "ppm" => write_ppm(canvas, &mut writer)?,
+        "pfm" => write_pfm(canvas, &mut writer)?,
+        _ => write_exr(canvas, &mut writer)?,
+    }
+    writer.flush()
+}
+
+/// The lowercase extension of `path` if it is one of the supported formats.
+pub fn format_of(path: &Path) -> Option<String> {
+    path.extension()
+        .and_then(|e| e.to_str())
+        .map(|e| e.to_ascii_lowercase())
+        .filter(|e| FORMATS.contains(&e.as_str()))
+}
+
+pub fn write_png(canvas: &Canvas, writer: &mut impl Write) -> io::Result<()> {
+    let mut encoder = png::Encoder::new(writer, canvas.width as u32, canvas.height as u32);
+    encoder.set_color(png::ColorType::Rgb);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder.set_source_srgb(png::SrgbRenderingIntent::Perceptual);
+    let data: Vec<u8> = canvas
+        .develop()
+        .iter()
+        .flat_map(|pixel| [pixel.red, pixel.green, pixel.blue])
+        .collect();
+    encoder
+        .write_header()
+        .and_then(|mut png_writer| png_writer.write_image_data(&data))
+        .map_err(io::Error::other)
+}
+
+/// Binary PPM (P6).
+pub fn write_ppm(canvas: &Canvas, writer: &mut impl Write) -> io::Result<()> {
+    write!(writer, "P6\n{} {}\n255\n", canvas.width, canvas.height)?;
+    for pixel in canvas.develop() {
+        writer.write_all(&[pixel.red, pixel.green, pixel.blue])?;
+    }
+    Ok(())
+}
+
+/// Little-endian color PFM. Its rows go from the bottom up.
+pub fn write_pfm(canvas: &Canvas, writer: &mut impl Write) -> io::Result<()> {
+    write!(writer, "PF\n{} {}\n-1.0\n", canvas.width, canvas.height)?;
+    let radiance = canvas.radiance();
+    for row in radiance.chunks(canvas.width).rev() {
+        for color in row {
+            for value in [color.red, color.green, color.blue] {
+                writer.write_all(&value.to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Uncompressed single-part scanline OpenEXR with 32-bit float channels.
+pub fn write_exr(canvas: &Canvas, writer: &mut impl Write) -> io::Result<()> {
+    const FLOAT: i32 = 2;
+    let width = canvas.width as i32;
+    let height = canvas.height as i32;
+
+    let mut header: Vec<u8> = Vec::new();
+    header.extend([0x76, 0x2f, 0x31, 0x01]);
+    header.extend(2_i32.to_le_bytes());
+
+    // channels have to be sorted by name
+    let mut channels = Vec::new();
+    for name in ["B", "G", "R"] {
+        channels.extend(name.as_bytes());
+        channels.push(0);
+        channels.extend(FLOAT.to_le_bytes());
+        // pLinear and three reserved bytes
+        channels.extend([0, 0, 0, 0]);
+        channels.extend(1_i32.to_le_bytes());
+        channels.extend(1_i32.to_le_bytes());
+    }
+    channels.push(0);
+    let window: Vec<u8> = [0, 0, width - 1, height - 1].iter().flat_map(|v| v.to_le_bytes()).collect();
+
+    write_attribute(&mut header, "channels", "chlist", &channels);
+    write_attribute(&mut header, "compression", "compression", &[0]);
+    write_attribute(&mut header, "dataWindow", "box2i", &window);
+    write_attribute(&mut header, "displayWindow", "box2i", &window);
+    write_attribute(&mut header, "lineOrder", "lineOrder", &[0]);
+    write_attribute(&mut header, "pixelAspectRatio", "float", &1_f32.to_le_bytes());
+    write_attribute(&mut header, "screenWindowCenter", "v2f", &[0; 8]);
+    write_attribute(&mut header, "screenWindowWidth", "float", &1_f32.to_le_bytes());
+    header.push(0);
+    writer.write_all(&header)?;
+
+    // every scanline block is its y, its size and the channels one after another
+    let line_size = canvas.width * 3 * 4;
+    let block_size = 4 + 4 + line_size;
+    let table_size = canvas.height * 8;
+    for y in 0..canvas.height {
+        let offset = (header.len() + table_size + y * block_size) as u64;
+        writer.write_all(&offset.to_le_bytes())?;
+    }
+    let radiance = canvas.radiance();
+    for (y, row) in radiance.chunks(canvas.width).enumerate() {
+        writer.write_all(&(y as i32).to_le_bytes())?;
+        writer.write_all(&(line_size as i32).to_le_bytes())?;
+        let channels: [fn(&Color) -> f32; 3] = [|c| c.blue, |c| c.green, |c| c.red];
+        for channel in channels {
+            for color in row {
+                writer.write_all(&channel(color).to_le_bytes())?;
+            }
+        }
+    }
+    Ok(())
+}
+
+fn write_attribute(header: &mut Vec<u8>, name: &str, kind: &str, value: &[u8]) {
+    header.extend(name.as_bytes());
+    header.push(0);
+    header.extend(kind.as_bytes());
+    header.push(0);
+    header.extend((value.len() as i32).to_le_bytes());
+    header.extend(value);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rayon::iter::ParallelIterator;
+
+    fn canvas() -> Canvas {
+        let mut canvas = Canvas::new(3, 2);
+        canvas.par_rows_mut().for_each(|mut row| {
+            for x in 0..3 {
+                row.draw_pixel(x, Color::new(x as f32, row.y as f32, 0.5));
+            }
+        });
+        canvas
+    }
+
+    #[test]
+    fn ppm_header_and_size() {
+        let mut data = Vec::new();
+        write_ppm(&canvas(), &mut data).unwrap();
+
+        assert!(data.starts_with(b"P6\n3 2\n255\n"));
+        assert!(data.len() == b"P6\n3 2\n255\n".len() + 3 * 2 * 3);
+    }
+
+    #[test]
+    fn pfm_starts_with_bottom_row() {
+        let mut data = Vec::new();
+        write_pfm(&canvas(), &mut data).unwrap();
+
+        let header = b"PF\n3 2\n-1.0\n";
+        let green = f32::from_le_bytes(data[header.len() + 4..header.len() + 8].try_into().unwrap());
+        assert!(green == 1.);
+    }
+
+    #[test]
+    fn exr_offsets_point_at_scanlines() {
+        let mut data = Vec::new();
+        write_exr(&canvas(), &mut data).unwrap();
+
+        assert!(data.starts_with(&[0x76, 0x2f, 0x31, 0x01]));
+        let line_block = 4 + 4 + 3 * 3 * 4;
+        let table_start = data.len() - 2 * line_block - 2 * 8;
+        let first = u64::from_le_bytes(data[table_start..table_start + 8].try_into().unwrap()) as usize;
+        assert!(first == table_start + 2 * 8);
+        let y = i32::from_le_bytes(data[first + line_block..first + line_block + 4].try_into().unwrap());
+        assert!(y == 1);
+    }
+
+    #[test]
+    fn png_roundtrip() {
+        let mut data = Vec::new();
+        write_png(&canvas(), &mut data).unwrap();
+
+        let decoder = png::Decoder::new(data.as_slice());
+        let reader = decoder.read_info().unwrap();
+        assert!(reader.info().width == 3);
+        assert!(reader.info().height == 2);
+    }
+}
@@ -1,29 +1,61 @@
-use std::time::Instant;
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Instant, SystemTime, UNIX_EPOCH};
 
-use minifb::{Key, Window, WindowOptions};
+use rayon::ThreadPoolBuilder;
+use minifb::{Key, KeyRepeat, MouseButton, MouseMode, Window, WindowOptions};
 
 mod primitives;
 mod canvas;
 mod camera;
 mod scene;
 mod surface;
+mod settings;
+mod tonemap;
+mod export;
+mod texture;
+mod cli;
+mod scene_file;
 
 use primitives::*;
-use primitives::vec3d::{Point3d, Vec3d};
 use canvas::Canvas;
-use camera::Camera;
+use cli::{Command, RenderOptions};
+use camera::{ApertureShape, Projection, SensorFit};
 use scene::Scene;
-use surface::mesh::Mesh;
-use surface::sphere::Sphere;
+use scene_file::SceneFile;
+use settings::RenderSettings;
 use surface::material::*;
 
 const WIDTH: usize = 800;
 const HEIGHT: usize = 600;
 
-fn main() {
-    let mut scene = init_scene();
+const DEFAULT_SCENE: &str = "scene/default.scene";
 
-    display(&mut scene);
+fn main() -> ExitCode {
+    let command = match cli::parse(std::env::args().skip(1)) {
+        Ok(command) => command,
+        Err(e) => {
+            eprintln!("error: {}\n\n{}", e, cli::USAGE);
+            return ExitCode::FAILURE
+        }
+    };
+    let result = match command {
+        Command::Help => {
+            println!("{}", cli::USAGE);
+            Ok(())
+        }
+        Command::Window { scene } => {
+            load_scene(scene.as_deref()).map(|file| display(file.scene, file.settings, scene.as_deref()))
+        }
+        Command::Render(options) => render_headless(&options),
+    };
+    match result {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("error: {}", e);
+            ExitCode::FAILURE
+        }
+    }
 }
 
 fn init_canvas() -> Canvas {
@@ -31,32 +63,44 @@ fn init_canvas() -> Canvas {
     canvas
 }
 
-fn init_scene() -> Scene {
-    let orig_direction = Vec3d {x: 5., y: 0., z: 0.};
-    let direction = orig_direction.rotate_x(0.);
-    let camera = Camera { location: Point3d { x: 2.1557255, y: 9.327328, z: 0.0 }, direction: Point3d { x: -2.0807352, y: -4.5464883, z: 0.0 } };
-    let material = Material { material_kind: MaterialKind::Diffuse, color: Color::new(1., 0.3, 0.3)};
-    let small_sphere = Sphere::new(Point3d { x: -5., y: -0.75, z: 0. }, 0.75, material);
-    let material = Material { material_kind: MaterialKind::Glossy, color: Color::new(1., 1., 1.)};
-    let small_sphere_2 = Sphere::new(Point3d { x: -5., y: 0.75, z: 0. }, 0.75, material);
-    let material = Material { material_kind: MaterialKind::Diffuse, color: Color::new(0.8, 0.8, 0.8)};
-    let big_sphere = Sphere::new(Point3d { x: -5., y: 0., z: -50. }, 49.25, material);
-    let mesh = Mesh::import("scene/monkey.obj");
-    let light_source = Vec3d::new(1., 1., 1.).normalize();
-    let scene = Scene {
-        spheres: vec![
-            small_sphere,
-            small_sphere_2,
-            big_sphere,
-        ],
-        meshes: vec![mesh],
-        camera,
-        light_source
-    };
-    scene
+fn load_scene(path: Option<&Path>) -> Result<SceneFile, String> {
+    scene_file::load(path.unwrap_or(Path::new(DEFAULT_SCENE)))
+}
+
+fn render_headless(options: &RenderOptions) -> Result<(), String> {
+    if export::format_of(&options.output).is_none() {
+        return Err(format!("Unsupported image format of {}, use png, ppm, pfm or exr.", options.output.display()))
+    }
+    if let Some(threads) = options.threads {
+        ThreadPoolBuilder::new()
+            .num_threads(threads)
+            .build_global()
+            .map_err(|e| format!("Could not start {} threads: {}", threads, e))?;
+    }
+    let SceneFile { scene, mut settings } = load_scene(options.scene.as_deref())?;
+    settings.samples_per_pixel = options.samples_per_pixel.unwrap_or(settings.samples_per_pixel);
+    settings.max_depth = options.max_depth.unwrap_or(settings.max_depth);
+    let mut canvas = Canvas::new(options.width, options.height);
+
+    let beginning = Instant::now();
+    for pass in 1..=options.passes {
+        scene.camera.render(&mut canvas, &scene, &settings);
+        println!("Pass {}/{} done", pass, options.passes);
+    }
+    println!(
+        "Rendered {} samples per pixel in {} ms",
+        u64::from(options.passes) * u64::from(settings.samples_per_pixel),
+        beginning.elapsed().as_millis()
+    );
+
+    export::save(&canvas, &options.output)
+        .map_err(|e| format!("Could not save {}: {}", options.output.display(), e))?;
+    println!("Saved {}", options.output.display());
+    Ok(())
 }
 
-fn display(scene: &mut Scene) {
+fn display(mut scene: Scene, mut settings: RenderSettings, scene_path: Option<&Path>) {
+    let scene = &mut scene;
     let mut window = Window::new(
         "Test - ESC to exit",
         WIDTH,
@@ -69,21 +113,147 @@ fn display(scene: &mut Scene) {
 
     let r_step = 0.05_f32;
     let m_step = 0.2_f32;
+    let zoom_step = 1.02_f32;
+    // radians per pixel of mouse movement
+    let mouse_sensitivity = 0.005_f32;
+    let mut last_mouse_pos: Option<(f32, f32)> = None;
+    let mut mouse_was_down = false;
+    let aperture_step = 0.01_f32;
+    let exposure_step = 0.25_f32;
+    // in this mode a left click focuses on the object under the cursor
+    let mut click_to_focus = false;
     // Limit to max ~60 fps update rate
     window.limit_update_rate(Some(std::time::Duration::from_micros(16600)));
 
     let mut canvas = init_canvas();
+    let mut last_camera = scene.camera;
+    let mut last_settings = settings;
+    let mut last_revision = scene.revision();
 
     while window.is_open() && !window.is_key_down(Key::Escape) {
         let beginning = Instant::now();
 
-        scene.camera.render(&mut canvas, &scene);
+        scene.camera.render(&mut canvas, scene, &settings);
+        let samples = u64::from(canvas.frames()) * u64::from(settings.samples_per_pixel);
         if window.is_key_down(Key::Left) {
-            scene.camera.rotate_x(r_step);
+            scene.camera.yaw(r_step);
         } else if window.is_key_down(Key::Right) {
-            scene.camera.rotate_x(-r_step);
+            scene.camera.yaw(-r_step);
+        }
+
+        if window.is_key_down(Key::Up) {
+            scene.camera.pitch(r_step);
+        } else if window.is_key_down(Key::Down) {
+            scene.camera.pitch(-r_step);
+        }
+
+        if window.is_key_down(Key::Q) {
+            scene.camera.roll(-r_step);
+        } else if window.is_key_down(Key::E) {
+            scene.camera.roll(r_step);
+        }
+
+        if window.is_key_down(Key::Equal) {
+            scene.camera.zoom(zoom_step);
+        } else if window.is_key_down(Key::Minus) {
+            scene.camera.zoom(1. / zoom_step);
+        }
+
+        if window.is_key_pressed(Key::F, KeyRepeat::No) {
+            scene.camera.sensor_fit = match scene.camera.sensor_fit {
+                SensorFit::Auto => SensorFit::Horizontal,
+                SensorFit::Horizontal => SensorFit::Vertical,
+                SensorFit::Vertical => SensorFit::Auto,
+            };
+        }
+
+        if window.is_key_down(Key::X) {
+            scene.camera.aperture_radius += aperture_step;
+        } else if window.is_key_down(Key::Z) {
+            scene.camera.aperture_radius = f32::max(0., scene.camera.aperture_radius - aperture_step);
+        }
+
+        if window.is_key_pressed(Key::B, KeyRepeat::No) {
+            scene.camera.aperture_shape = match scene.camera.aperture_shape {
+                ApertureShape::Circular => ApertureShape::Polygonal { blades: 5 },
+                ApertureShape::Polygonal { blades: 5 } => ApertureShape::Polygonal { blades: 6 },
+                ApertureShape::Polygonal { blades: 6 } => ApertureShape::Polygonal { blades: 8 },
+                ApertureShape::Polygonal { .. } => ApertureShape::Circular,
+            };
+        }
+
+        if window.is_key_pressed(Key::RightBracket, KeyRepeat::No) {
+            settings.increase_samples();
+        } else if window.is_key_pressed(Key::LeftBracket, KeyRepeat::No) {
+            settings.decrease_samples();
+        }
+
+        if window.is_key_pressed(Key::Period, KeyRepeat::No) {
+            settings.increase_depth();
+        } else if window.is_key_pressed(Key::Comma, KeyRepeat::No) {
+            settings.decrease_depth();
+        }
+
+        if window.is_key_pressed(Key::V, KeyRepeat::No) {
+            settings.view = settings.view.next();
+        }
+
+        if window.is_key_pressed(Key::T, KeyRepeat::No) {
+            canvas.tone_mapper.operator = canvas.tone_mapper.operator.next();
+        }
+
+        if window.is_key_pressed(Key::PageUp, KeyRepeat::Yes) {
+            canvas.tone_mapper.exposure += exposure_step;
+        } else if window.is_key_pressed(Key::PageDown, KeyRepeat::Yes) {
+            canvas.tone_mapper.exposure -= exposure_step;
+        }
+
+        if window.is_key_pressed(Key::F12, KeyRepeat::No) {
+            save_screenshot(&canvas);
+        }
+
+        if window.is_key_pressed(Key::P, KeyRepeat::No) {
+            scene.camera.projection = match scene.camera.projection {
+                Projection::Perspective => Projection::Orthographic,
+                Projection::Orthographic => Projection::Equirectangular,
+                Projection::Equirectangular => Projection::Perspective,
+            };
+        }
+
+        if window.is_key_pressed(Key::R, KeyRepeat::No) {
+            // picks up edits of the scene file while keeping the view
+            match load_scene(scene_path) {
+                Ok(file) => scene.edit_objects(|objects| *objects = file.scene.world.into_objects()),
+                Err(e) => eprintln!("error: {}", e),
+            }
+        }
+
+        if window.is_key_pressed(Key::C, KeyRepeat::No) {
+            click_to_focus = !click_to_focus;
+            println!("Click to focus: {}", if click_to_focus { "on" } else { "off" });
         }
 
+        let mouse_pos = window.get_mouse_pos(MouseMode::Discard);
+        let mouse_down = window.get_mouse_down(MouseButton::Left);
+        if click_to_focus {
+            if let Some((x, y)) = mouse_pos
+                && mouse_down
+                && !mouse_was_down
+            {
+                focus_under_cursor(scene, x, y);
+            }
+        } else if mouse_down {
+            // dragging with the left mouse button looks around
+            if let (Some((x, y)), Some((last_x, last_y))) = (mouse_pos, last_mouse_pos) {
+                scene.camera.yaw((last_x - x) * mouse_sensitivity);
+                scene.camera.pitch((last_y - y) * mouse_sensitivity);
+            }
+            last_mouse_pos = mouse_pos;
+        } else {
+            last_mouse_pos = None;
+        }
+        mouse_was_down = mouse_down;
+
         if window.is_key_down(Key::W) {
             scene.camera.move_forward(m_step);
         } else if window.is_key_down(Key::S) {
@@ -95,6 +265,20 @@ fn display(scene: &mut Scene) {
         } else if window.is_key_down(Key::D) {
             scene.camera.move_left(-m_step);
         }
+
+        if window.is_key_down(Key::Space) {
+            scene.camera.move_up(m_step);
+        } else if window.is_key_down(Key::LeftShift) {
+            scene.camera.move_up(-m_step);
+        }
+
+        // the accumulated image is only valid for an unchanged view and scene
+        if scene.camera != last_camera || settings != last_settings || scene.revision() != last_revision {
+            canvas.reset();
+            last_camera = scene.camera;
+            last_settings = settings;
+            last_revision = scene.revision();
+        }
         
         window
             .update_with_buffer(&canvas.buffer, canvas.width, canvas.height)
@@ -104,10 +288,41 @@ fn display(scene: &mut Scene) {
         if elapsed > 1000 {
             println!("Frame duration: {} ms", elapsed);
         }
-        let fps = 1000 / elapsed;
-        let s = format!("{} FPS", fps);
+        let fps = 1000 / u128::max(elapsed, 1);
+        let s = format!(
+            "{} FPS | {} spp | depth {} | {} samples | {} {:+} EV | {} view",
+            fps,
+            settings.samples_per_pixel,
+            settings.max_depth,
+            samples,
+            canvas.tone_mapper.operator,
+            canvas.tone_mapper.exposure,
+            settings.view,
+        );
         window.set_title(s.as_str());
-    
-        canvas.clear();
     }
-}
\ No newline at end of file
+}
+
+/// Sets the focus distance to the primary hit under the given window position.
+fn focus_under_cursor(scene: &mut Scene, x: f32, y: f32) {
+    let ray = scene.camera.get_pinhole_ray(x, y, WIDTH, HEIGHT);
+    match scene.hit_distance(&ray) {
+        Some(distance) => {
+            scene.camera.focus_distance = distance;
+            println!("Focus distance: {}", distance);
+        }
+        None => println!("Nothing to focus on"),
+    }
+}
+
+/// Saves the current image both tone mapped and as linear HDR.
+fn save_screenshot(canvas: &Canvas) {
+    let timestamp = SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or_default();
+    for extension in ["png", "exr"] {
+        let path = PathBuf::from(format!("screenshot-{timestamp}.{extension}"));
+        match export::save(canvas, &path) {
+            Ok(()) => println!("Saved {}", path.display()),
+            Err(e) => println!("Could not save {}: {}", path.display(), e),
+        }
+    }
+}
@@ -1,55 +1,135 @@
 use std::ops;
 
-pub struct Matrix3x3 {
-    a11: f32, a12: f32, a13: f32,
-    a21: f32, a22: f32, a23: f32,
-    a31: f32, a32: f32, a33: f32,
+use super::vec3d::{Point3d, Vec3d};
+
+/// Row-major 4x4 matrix for affine transformations of homogeneous coordinates.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Matrix4x4 {
+    pub(crate) m: [[f32; 4]; 4],
 }
 
+impl Matrix4x4 {
+    pub fn new(m: [[f32; 4]; 4]) -> Matrix4x4 {
+        Matrix4x4 { m }
+    }
 
-pub struct Matrix3x1 {
-    pub a31: f32,
-    pub a11: f32,
-    pub a21: f32,
-}
+    pub fn identity() -> Matrix4x4 {
+        Matrix4x4::new([
+            [1., 0., 0., 0.],
+            [0., 1., 0., 0.],
+            [0., 0., 1., 0.],
+            [0., 0., 0., 1.],
+        ])
+    }
+
+    pub fn transpose(&self) -> Matrix4x4 {
+        let mut m = [[0_f32; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = self.m[j][i];
+            }
+        }
+        Matrix4x4 { m }
+    }
+
+    /// Gauss-Jordan elimination with partial pivoting.
+    /// Returns `None` for singular matrices.
+    pub fn inverse(&self) -> Option<Matrix4x4> {
+        let mut a = self.m;
+        let mut inv = Matrix4x4::identity().m;
+        for column in 0..4 {
+            let pivot = (column..4)
+                .max_by(|&i, &j| a[i][column].abs().total_cmp(&a[j][column].abs()))
+                .unwrap();
+            if a[pivot][column].abs() < f32::EPSILON {
+                return None
+            }
+            a.swap(column, pivot);
+            inv.swap(column, pivot);
 
-impl Matrix3x3 {
-    pub fn new(a11: f32, a12: f32, a13: f32,
-        a21: f32, a22: f32, a23: f32,
-        a31: f32, a32: f32, a33: f32) -> Matrix3x3 {
-            Matrix3x3 {
-                a11, a12, a13,
-                a21, a22, a23,
-                a31, a32, a33,
+            let pivot_inv = 1. / a[column][column];
+            for j in 0..4 {
+                a[column][j] *= pivot_inv;
+                inv[column][j] *= pivot_inv;
+            }
+            for row in 0..4 {
+                if row == column {
+                    continue
+                }
+                let factor = a[row][column];
+                for j in 0..4 {
+                    a[row][j] -= factor * a[column][j];
+                    inv[row][j] -= factor * inv[column][j];
+                }
             }
         }
+        Some(Matrix4x4 { m: inv })
+    }
+
+    pub fn transform_point(&self, p: Point3d) -> Point3d {
+        let m = &self.m;
+        let x = m[0][0] * p.x + m[0][1] * p.y + m[0][2] * p.z + m[0][3];
+        let y = m[1][0] * p.x + m[1][1] * p.y + m[1][2] * p.z + m[1][3];
+        let z = m[2][0] * p.x + m[2][1] * p.y + m[2][2] * p.z + m[2][3];
+        let w = m[3][0] * p.x + m[3][1] * p.y + m[3][2] * p.z + m[3][3];
+        if w == 1. {
+            Point3d::new(x, y, z)
+        } else {
+            Point3d::new(x, y, z) / w
+        }
+    }
+
+    /// Ignores the translation part.
+    pub fn transform_vector(&self, v: Vec3d) -> Vec3d {
+        let m = &self.m;
+        Vec3d::new(
+            m[0][0] * v.x + m[0][1] * v.y + m[0][2] * v.z,
+            m[1][0] * v.x + m[1][1] * v.y + m[1][2] * v.z,
+            m[2][0] * v.x + m[2][1] * v.y + m[2][2] * v.z,
+        )
+    }
 }
 
-impl Matrix3x1 {
-    pub fn new(
-        a11: f32,
-        a21: f32,
-        a31: f32) -> Matrix3x1 {
-            Matrix3x1 {
-                a11,
-                a21,
-                a31,
+impl ops::Mul<Matrix4x4> for Matrix4x4 {
+    type Output = Matrix4x4;
+
+    fn mul(self, rhs: Matrix4x4) -> Self::Output {
+        let mut m = [[0_f32; 4]; 4];
+        for (i, row) in m.iter_mut().enumerate() {
+            for (j, value) in row.iter_mut().enumerate() {
+                *value = (0..4).map(|k| self.m[i][k] * rhs.m[k][j]).sum();
             }
         }
+        Matrix4x4 { m }
+    }
 }
 
-impl ops::Mul<Matrix3x1> for Matrix3x3 {
-    type Output = Matrix3x1;
+#[cfg(test)]
+mod tests {
+    use super::Matrix4x4;
 
-    fn mul(self, rhs: Matrix3x1) -> Self::Output {
-        let a11 = self.a11 * rhs.a11 + self.a12 * rhs.a21 + self.a13 * rhs.a31;
-        let a21 = self.a21 * rhs.a11 + self.a22 * rhs.a21 + self.a23 * rhs.a31;
-        let a31 = self.a31 * rhs.a11 + self.a32 * rhs.a21 + self.a33 * rhs.a31;
+    #[test]
+    fn inverse() {
+        let matrix = Matrix4x4::new([
+            [2., 0., 0., 1.],
+            [0., 0., -3., 2.],
+            [0., 1., 0., 3.],
+            [0., 0., 0., 1.],
+        ]);
+        let product = matrix * matrix.inverse().unwrap();
 
-        Matrix3x1 {
-            a11,
-            a21,
-            a31
+        for i in 0..4 {
+            for j in 0..4 {
+                let expected = if i == j { 1. } else { 0. };
+                assert!((product.m[i][j] - expected).abs() < 1e-6);
+            }
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn singular_has_no_inverse() {
+        let matrix = Matrix4x4::new([[0.; 4]; 4]);
+
+        assert!(matrix.inverse().is_none());
+    }
+}
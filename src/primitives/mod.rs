@@ -1,11 +1,11 @@
 use core::convert::From;
-use std::mem::transmute_copy;
 use vec3d::{Point3d, Vec3d};
 use std::ops;
 
 pub mod vec3d;
 pub mod rand;
-mod matrix;
+pub mod matrix;
+pub mod transform;
 
 #[derive(Debug)]
 pub struct Ray {
@@ -15,15 +15,24 @@ pub struct Ray {
 
 #[derive(Debug, Clone, Copy)]
 pub struct Color {
-    pub(super) red: f32,
-    pub(super) green: f32,
-    pub(super) blue: f32,
+    pub(crate) red: f32,
+    pub(crate) green: f32,
+    pub(crate) blue: f32,
 }
 
 impl Color {
     pub fn new(red: f32, green: f32, blue: f32) -> Color {
         Color { red, green, blue }
     }
+
+    /// Relative luminance of linear Rec. 709 primaries.
+    pub fn luminance(&self) -> f32 {
+        0.2126 * self.red + 0.7152 * self.green + 0.0722 * self.blue
+    }
+
+    pub fn map(&self, f: impl Fn(f32) -> f32) -> Color {
+        Color::new(f(self.red), f(self.green), f(self.blue))
+    }
 }
 
 impl ops::Mul<Color> for Color {
@@ -60,21 +69,35 @@ impl ops::Add<Color> for Color {
     }
 }
 
+/// Display-ready 8-bit color. Values outside [0, 1] are clamped.
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub struct Color8b {
-    blue: u8,
-    green: u8,
-    red: u8,
-    alpha: u8,
+    pub(crate) blue: u8,
+    pub(crate) green: u8,
+    pub(crate) red: u8,
+    pub(crate) alpha: u8,
 }
 
-impl From<Color> for u32 {
+impl From<Color> for Color8b {
     fn from(color: Color) -> Self {
-        let color8b = Color8b {
-            red: (color.red * 256.) as u8,
-            green: (color.green * 256.) as u8,
-            blue: (color.blue * 256.) as u8,
+        let quantize = |value: f32| (value.clamp(0., 1.) * 255. + 0.5) as u8;
+        Color8b {
+            red: quantize(color.red),
+            green: quantize(color.green),
+            blue: quantize(color.blue),
             alpha: 0_u8,
-        };
-        unsafe { transmute_copy(&color8b) }
+        }
+    }
+}
+
+impl From<Color8b> for u32 {
+    fn from(color: Color8b) -> Self {
+        u32::from_le_bytes([color.blue, color.green, color.red, color.alpha])
+    }
+}
+
+impl From<Color> for u32 {
+    fn from(color: Color) -> Self {
+        Color8b::from(color).into()
     }
 }
@@ -4,4 +4,15 @@ pub fn get_random_float_neg_pos(multiplier: f32) -> f32 {
 
 pub fn get_random_float() -> f32 {
     fastrand::f32()
-}
\ No newline at end of file
+}
+
+/// Uniformly distributed point inside the unit disk.
+pub fn get_random_in_unit_disk() -> (f32, f32) {
+    loop {
+        let x = get_random_float_neg_pos(1.);
+        let y = get_random_float_neg_pos(1.);
+        if x * x + y * y < 1. {
+            return (x, y)
+        }
+    }
+}
@@ -0,0 +1,210 @@
+use std::ops;
+
+use super::matrix::Matrix4x4;
+use super::vec3d::{Point3d, Vec3d};
+
+/// Affine transformation that keeps its inverse next to it, so going back and
+/// forth between spaces never needs a matrix inversion.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Transform {
+    matrix: Matrix4x4,
+    inverse: Matrix4x4,
+    /// The inverse transpose, which transforms normals.
+    normal: Matrix4x4,
+}
+
+impl Transform {
+    fn new(matrix: Matrix4x4, inverse: Matrix4x4) -> Transform {
+        Transform { matrix, inverse, normal: inverse.transpose() }
+    }
+
+    pub fn identity() -> Transform {
+        Transform::new(Matrix4x4::identity(), Matrix4x4::identity())
+    }
+
+    /// Returns `None` if the matrix is not invertible.
+    // part of the public transform toolkit, the scene file has no caller yet
+    #[allow(dead_code)]
+    pub fn from_matrix(matrix: Matrix4x4) -> Option<Transform> {
+        let inverse = matrix.inverse()?;
+        Some(Transform::new(matrix, inverse))
+    }
+
+    pub fn translate(offset: Vec3d) -> Transform {
+        Transform::new(
+            Matrix4x4::new([
+                [1., 0., 0., offset.x],
+                [0., 1., 0., offset.y],
+                [0., 0., 1., offset.z],
+                [0., 0., 0., 1.],
+            ]),
+            Matrix4x4::new([
+                [1., 0., 0., -offset.x],
+                [0., 1., 0., -offset.y],
+                [0., 0., 1., -offset.z],
+                [0., 0., 0., 1.],
+            ]),
+        )
+    }
+
+    /// Panics on a zero scale factor.
+    pub fn scale(x: f32, y: f32, z: f32) -> Transform {
+        assert!(x != 0. && y != 0. && z != 0., "Scale factors must not be zero!");
+        Transform::new(
+            Matrix4x4::new([
+                [x, 0., 0., 0.],
+                [0., y, 0., 0.],
+                [0., 0., z, 0.],
+                [0., 0., 0., 1.],
+            ]),
+            Matrix4x4::new([
+                [1. / x, 0., 0., 0.],
+                [0., 1. / y, 0., 0.],
+                [0., 0., 1. / z, 0.],
+                [0., 0., 0., 1.],
+            ]),
+        )
+    }
+
+    /// Counterclockwise rotation by `theta` radians around `axis`.
+    pub fn rotate(axis: Vec3d, theta: f32) -> Transform {
+        let a = axis.normalize();
+        let (sin, cos) = theta.sin_cos();
+        let t = 1. - cos;
+        let matrix = Matrix4x4::new([
+            [t * a.x * a.x + cos, t * a.x * a.y - sin * a.z, t * a.x * a.z + sin * a.y, 0.],
+            [t * a.x * a.y + sin * a.z, t * a.y * a.y + cos, t * a.y * a.z - sin * a.x, 0.],
+            [t * a.x * a.z - sin * a.y, t * a.y * a.z + sin * a.x, t * a.z * a.z + cos, 0.],
+            [0., 0., 0., 1.],
+        ]);
+        // rotations are orthogonal
+        Transform::new(matrix, matrix.transpose())
+    }
+
+    /// Places the origin at `eye` and turns the local axes so that X looks at
+    /// `target`, Y points to the left and Z up, like the world axes of a scene.
+    /// Returns `None` if `up` is parallel to the viewing direction.
+    pub fn look_at(eye: Point3d, target: Point3d, up: Vec3d) -> Option<Transform> {
+        let forward = (target - eye).normalize();
+        let left = up.cross(&forward);
+        if left.len_squared() < f32::EPSILON {
+            return None
+        }
+        let left = left.normalize();
+        let up = forward.cross(&left);
+        let rotation = Matrix4x4::new([
+            [forward.x, left.x, up.x, 0.],
+            [forward.y, left.y, up.y, 0.],
+            [forward.z, left.z, up.z, 0.],
+            [0., 0., 0., 1.],
+        ]);
+        let rotation = Transform::new(rotation, rotation.transpose());
+        Some(Transform::translate(eye) * rotation)
+    }
+
+    pub fn inverse(&self) -> Transform {
+        Transform::new(self.inverse, self.matrix)
+    }
+
+    #[allow(dead_code)]
+    pub fn transpose(&self) -> Transform {
+        Transform::new(self.matrix.transpose(), self.inverse.transpose())
+    }
+
+    pub fn apply_point(&self, point: Point3d) -> Point3d {
+        self.matrix.transform_point(point)
+    }
+
+    pub fn apply_vector(&self, vector: Vec3d) -> Vec3d {
+        self.matrix.transform_vector(vector)
+    }
+
+    /// Normals are transformed by the inverse transpose to stay perpendicular
+    /// to the surface under non-uniform scaling. The result is not normalized.
+    pub fn apply_normal(&self, normal: Vec3d) -> Vec3d {
+        self.normal.transform_vector(normal)
+    }
+}
+
+/// `a * b` applies `b` first, then `a`.
+impl ops::Mul<Transform> for Transform {
+    type Output = Transform;
+
+    fn mul(self, rhs: Transform) -> Self::Output {
+        Transform::new(self.matrix * rhs.matrix, rhs.inverse * self.inverse)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_2;
+
+    use super::Transform;
+    use crate::primitives::matrix::Matrix4x4;
+    use crate::primitives::vec3d::{assert_close, Point3d, Vec3d};
+
+    #[test]
+    fn composition_applies_right_first() {
+        let transform = Transform::translate(Vec3d::new(1., 0., 0.)) * Transform::rotate(Vec3d::new(0., 0., 1.), FRAC_PI_2);
+        let point = transform.apply_point(Point3d::new(1., 0., 0.));
+
+        assert_close(point, Point3d::new(1., 1., 0.));
+    }
+
+    #[test]
+    fn inverse_undoes_transform() {
+        let transform = Transform::translate(Vec3d::new(1., 2., 3.))
+            * Transform::rotate(Vec3d::new(1., 1., 0.), 0.7)
+            * Transform::scale(2., 3., 4.);
+        let point = Point3d::new(-1., 5., 0.5);
+        let back = transform.inverse().apply_point(transform.apply_point(point));
+
+        assert_close(back, point);
+    }
+
+    #[test]
+    fn vectors_ignore_translation() {
+        let transform = Transform::translate(Vec3d::new(1., 2., 3.));
+
+        assert_close(transform.apply_vector(Vec3d::new(0., 0., 1.)), Vec3d::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn normals_stay_perpendicular_under_scaling() {
+        let transform = Transform::scale(4., 1., 1.);
+        let tangent = transform.apply_vector(Vec3d::new(1., -1., 0.));
+        let normal = transform.apply_normal(Vec3d::new(1., 1., 0.));
+
+        assert!(tangent.dot(&normal).abs() < 1e-6);
+    }
+
+    #[test]
+    fn from_matrix_inverts() {
+        let transform = Transform::translate(Vec3d::new(1., 2., 3.)) * Transform::scale(2., 3., 4.);
+        let from_matrix = Transform::from_matrix(transform.matrix).unwrap();
+        let point = Point3d::new(-1., 5., 0.5);
+
+        assert_close(from_matrix.inverse().apply_point(point), transform.inverse().apply_point(point));
+        assert!(Transform::from_matrix(Matrix4x4::new([[0.; 4]; 4])).is_none());
+    }
+
+    #[test]
+    fn transpose_of_rotation_is_inverse() {
+        let rotation = Transform::rotate(Vec3d::new(1., 1., 0.), 0.7);
+        let vector = Vec3d::new(-1., 5., 0.5);
+
+        assert_close(rotation.transpose().apply_vector(vector), rotation.inverse().apply_vector(vector));
+        assert_close(rotation.transpose().inverse().apply_vector(vector), rotation.apply_vector(vector));
+    }
+
+    #[test]
+    fn look_at() {
+        let eye = Point3d::new(1., 1., 0.);
+        let transform = Transform::look_at(eye, Point3d::new(1., 5., 0.), Vec3d::new(0., 0., 1.)).unwrap();
+
+        assert_close(transform.apply_point(Point3d::new(0., 0., 0.)), eye);
+        assert_close(transform.apply_vector(Vec3d::new(1., 0., 0.)), Vec3d::new(0., 1., 0.));
+        assert_close(transform.apply_vector(Vec3d::new(0., 1., 0.)), Vec3d::new(-1., 0., 0.));
+        assert_close(transform.apply_vector(Vec3d::new(0., 0., 1.)), Vec3d::new(0., 0., 1.));
+    }
+}
@@ -1,8 +1,9 @@
+use std::f32::consts::TAU;
 use std::ops;
 
-use super::{matrix::{Matrix3x3, Matrix3x1}, rand};
+use super::rand;
 
-#[derive(Debug,Copy,Clone)]
+#[derive(Debug,Copy,Clone,PartialEq)]
 pub struct Point3d {
     pub(crate) x: f32,
     pub(crate) y: f32,
@@ -41,30 +42,31 @@ impl Vec3d {
         self.x * other.x + self.y * other.y + self.z * other.z
     }
 
-    pub fn rotate_x(&self, theta: f32) -> Vec3d {
-        let rotate_matrix = Matrix3x3::new(
-            theta.cos(), -(theta.sin()), 0.,
-            theta.sin(), theta.cos(), 0.,
-            0., 0., 1.
-        );
-
-        let vec_matrix = Matrix3x1::new(
-            self.x,
-            self.y,
-            self.z
-        );
-
-        let result_matrix = rotate_matrix * vec_matrix;
+    pub fn min(&self, other: &Vec3d) -> Vec3d {
+        Vec3d::new(f32::min(self.x, other.x), f32::min(self.y, other.y), f32::min(self.z, other.z))
+    }
 
-        Vec3d::new(result_matrix.a11, result_matrix.a21, result_matrix.a31)
+    pub fn max(&self, other: &Vec3d) -> Vec3d {
+        Vec3d::new(f32::max(self.x, other.x), f32::max(self.y, other.y), f32::max(self.z, other.z))
     }
 
-    pub fn random() -> Vec3d {
-        Vec3d::new(rand::get_random_float_neg_pos(1.0), rand::get_random_float_neg_pos(1.0), rand::get_random_float_neg_pos(1.0))
+    /// Two unit vectors perpendicular to this unit vector and to each other.
+    pub fn orthonormal_basis(&self) -> (Vec3d, Vec3d) {
+        // branchless construction by Duff et al.
+        let sign = 1_f32.copysign(self.z);
+        let a = -1. / (sign + self.z);
+        let b = self.x * self.y * a;
+        let tangent = Vec3d::new(1. + sign * self.x * self.x * a, sign * b, -sign * self.x);
+        let bitangent = Vec3d::new(b, sign + self.y * self.y * a, -self.y);
+        (tangent, bitangent)
     }
 
+    /// Uniformly distributed on the unit sphere.
     pub fn random_unit() -> Vec3d {
-        Self::random().normalize()
+        let z = rand::get_random_float_neg_pos(1.0);
+        let (sin_phi, cos_phi) = (rand::get_random_float() * TAU).sin_cos();
+        let r = (1. - z * z).max(0.).sqrt();
+        Vec3d::new(r * cos_phi, r * sin_phi, z)
     }
 }
 
@@ -108,6 +110,18 @@ impl ops::Sub<Vec3d> for Vec3d {
     }
 }
 
+impl ops::Index<usize> for Vec3d {
+    type Output = f32;
+    fn index(&self, axis: usize) -> &Self::Output {
+        match axis {
+            0 => &self.x,
+            1 => &self.y,
+            2 => &self.z,
+            _ => panic!("Axis index {axis} is out of range!"),
+        }
+    }
+}
+
 impl ops::Mul<Vec3d> for Vec3d {
     type Output = f32;
     fn mul(self, rhs: Vec3d) -> Self::Output {
@@ -115,9 +129,15 @@ impl ops::Mul<Vec3d> for Vec3d {
     }
 }
 
+/// Fails unless two vectors only differ by rounding errors.
+#[cfg(test)]
+pub fn assert_close(a: Vec3d, b: Vec3d) {
+    assert!((a - b).len() < 1e-5, "{:?} != {:?}", a, b);
+}
+
 #[cfg(test)]
 mod tests {
-    use crate::Vec3d;
+    use super::Vec3d;
 
     #[test]
     fn len_squared() {
@@ -198,4 +218,17 @@ mod tests {
         assert!(cross.y == 0.);
         assert!(cross.z == 1.);
     }
+
+    #[test]
+    fn orthonormal_basis() {
+        for normal in [Vec3d::new(0., 0., 1.), Vec3d::new(0., 0., -1.), Vec3d::new(1., 2., -3.).normalize()] {
+            let (tangent, bitangent) = normal.orthonormal_basis();
+
+            assert!((tangent.len() - 1.).abs() < 1e-5);
+            assert!((bitangent.len() - 1.).abs() < 1e-5);
+            assert!(tangent.dot(&normal).abs() < 1e-5);
+            assert!(bitangent.dot(&normal).abs() < 1e-5);
+            assert!(tangent.dot(&bitangent).abs() < 1e-5);
+        }
+    }
 }
@@ -1,19 +1,55 @@
 use crate::primitives::vec3d::Vec3d;
-use crate::surface::material::MaterialKind;
-use crate::primitives::{Color, Ray};
-use crate::surface::mesh::Mesh;
-use crate::surface::sphere::Sphere;
+
+use crate::primitives::{rand, Color, Ray};
 use crate::camera::Camera;
-use crate::surface::triangle::Triangle;
-use crate::surface::{Object, Surface};
+use crate::settings::View;
+use crate::surface::list::HittableList;
+use crate::surface::bsdf::{Sample, Shading};
+use crate::surface::{HitRecord, Hittable};
+
+/// Distance bounced rays start off the surface they leave.
+const SURFACE_OFFSET: f32 = 1e-4;
 
 pub struct Scene {
-    pub spheres: Vec<Sphere>,
-    pub meshes: Vec<Mesh>,
+    pub world: HittableList,
     pub camera: Camera,
     pub light_source: Vec3d,
+    revision: u64,
+}
+
+impl Scene {
+    pub fn new(objects: Vec<Box<dyn Hittable>>, camera: Camera, light_source: Vec3d) -> Scene {
+        Scene {
+            world: HittableList::new(objects),
+            camera,
+            light_source,
+            revision: 0,
+        }
+    }
+
+    /// Changes the objects of the world, see `HittableList::edit`.
+    pub fn edit_objects(&mut self, edit: impl FnOnce(&mut Vec<Box<dyn Hittable>>)) {
+        self.world.edit(edit);
+        self.revision += 1;
+    }
+
+    /// Changes every time the objects are edited.
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    /// Distance to the closest object along the ray.
+    pub fn hit_distance(&self, ray: &Ray) -> Option<f32> {
+        self.world.intersect(ray).map(|hit| hit.t)
+    }
+
+    /// Shadow query: whether any object blocks the ray closer than `t_max`.
+    pub fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.world.occluded(ray, t_max)
+    }
 }
 
+#[allow(dead_code)]
 fn get_background_color(ray: &Ray) -> Color {
     let norm_dir = ray.direction.normalize();
     let color = Color::new(norm_dir.x, norm_dir.y, norm_dir.z);
@@ -21,8 +57,7 @@ fn get_background_color(ray: &Ray) -> Color {
 }
 
 fn get_lightness(ray: &Ray, scene: &Scene) -> Color {
-    if get_any_intersection(ray, &scene.spheres)
-    || get_any_intersection_with_objects(ray, &scene.meshes) {
+    if scene.occluded(ray, f32::MAX) {
         return Color::new(0., 0., 0.)
     }
     let norm_dir = ray.direction.normalize();
@@ -31,119 +66,125 @@ fn get_lightness(ray: &Ray, scene: &Scene) -> Color {
     return color;
 }
 
-fn get_any_intersection<T: Surface>(ray: &Ray, surfaces: &[T]) -> bool {
-    for surface in surfaces {
-        let intersection = surface.intersect(ray);
-        match intersection {
-            Some(_) => return true,
-            None => {}
+//TODO move it to camera?
+pub fn get_ray_color(ray: &Ray, scene: &Scene, depth: u8) -> Color {
+    if depth > 0 && let Some(hit) = scene.world.intersect(ray) {
+        let material = hit.material;
+        let transmittance = transmittance(ray, &hit);
+        if material.dissolve < 1. && rand::get_random_float() >= material.dissolve {
+            // the ray passes through the transparent part of the surface,
+            // starting behind it so that it does not hit its back right away
+            let origin = hit.point - hit.geometric_normal * SURFACE_OFFSET;
+            let through = Ray { origin, direction: ray.direction };
+            return transmittance * get_ray_color(&through, scene, depth - 1)
         }
+        let Some((bounce_ray, attenuation)) = reflect(ray, &hit) else {
+            return transmittance * material.emission
+        };
+        return transmittance * (material.emission + attenuation * get_ray_color(&bounce_ray, scene, depth - 1))
     }
-    return false
+    // return get_background_color(ray);
+    return get_lightness(ray, scene)
 }
 
-fn get_any_intersection_with_objects<T: Object>(ray: &Ray, surfaces: &[T]) -> bool {
-    for surface in surfaces {
-        let (_, triangle) = surface.intersect(ray);
-        match triangle {
-            Some(_) => return true,
-            None => {}
+/// False colors of the first hit for the debugging views, black for a miss.
+pub fn get_debug_color(ray: &Ray, scene: &Scene, view: View) -> Color {
+    let Some(hit) = scene.world.intersect(ray) else {
+        return Color::new(0., 0., 0.)
+    };
+    match view {
+        View::Shaded | View::Normals => {
+            let n = hit.shading_normal;
+            Color::new(n.x * 0.5 + 0.5, n.y * 0.5 + 0.5, n.z * 0.5 + 0.5)
         }
-    }
-    return false
-}
-
-fn get_closest_ditance<'a, 'b, T: Surface>(ray: &'a Ray, surfaces: &'b [T]) -> (f32, Option<&'b T>) {
-    let mut closest_surface  = None;
-    let mut closest_distance = f32::MAX;
-    for surface in surfaces {
-        let distance = surface.intersect(ray);
-        match distance {
-            Some(new_distance) => {
-                closest_distance = f32::min(closest_distance, new_distance);
-                if closest_distance == new_distance {
-                    closest_surface = Some(surface);
-                }
-            }
-            None => continue
+        View::Uv => Color::new(hit.uv.0.rem_euclid(1.), hit.uv.1.rem_euclid(1.), 0.),
+        View::Barycentrics => {
+            let (u, v) = hit.barycentrics;
+            Color::new(1. - u - v, u, v)
+        }
+        View::Primitives => {
+            // scatter neighbouring ids over distinct colors
+            let hash = (hit.primitive_id as u32 + 1).wrapping_mul(0x9e37_79b9);
+            let channel = |shift: u32| ((hash >> shift) & 0xff) as f32 / 255.;
+            Color::new(channel(8), channel(16), channel(24))
         }
     }
-    return (closest_distance, closest_surface)
 }
 
-fn get_closest_ditance_to_object<'a, 'b, T: Object>(ray: &'a Ray, objects: &'b [T]) -> (f32, Option<&'b Triangle>) {
-    let mut closest_surface  = None;
-    let mut closest_distance = f32::MAX;
-    for object in objects {
-        let (new_distance, triangle) = object.intersect(ray);
-        match triangle {
-            Some(_) => {
-                closest_distance = f32::min(closest_distance, new_distance);
-                if closest_distance == new_distance {
-                    closest_surface = triangle;
-                }
-            }
-            None => continue
-        }
+/// Beer–Lambert attenuation along a ray that leaves an absorbing medium
+/// at the hit, white for every other hit.
+fn transmittance(ray: &Ray, hit: &HitRecord) -> Color {
+    if hit.front_face {
+        return Color::new(1., 1., 1.)
     }
-    return (closest_distance, closest_surface)
+    let distance = hit.t * ray.direction.len();
+    hit.material.absorption.map(|absorption| f32::exp(-absorption * distance))
 }
 
-//TODO move it to camera?
-pub fn get_ray_color(ray: &Ray, scene: &Scene, depth: u8) -> Color {
-    if depth > 0 {
-        let (dist_to_sphere, sphere) = get_closest_ditance(ray, &scene.spheres);
-        let (dist_to_mesh, triangle) = get_closest_ditance_to_object(ray, &scene.meshes);
-
-        if dist_to_sphere < dist_to_mesh {
-            match sphere {
-                Some(surface) => {
-                    let (material, bounce_ray) = reflect(ray, dist_to_sphere, surface);
-                    return material.color * get_ray_color(&bounce_ray, scene, depth - 1)
-                },
-                None => {},
-            }
-        } else {
-            match triangle {
-                Some(mesh) => {
-                    let (material, bounce_ray) = reflect(ray, dist_to_mesh, mesh);
-                    return material.color * get_ray_color(&bounce_ray, scene, depth - 1)
-                },
-                None => {},
-            }
-        }
-    }
-    // return get_background_color(ray);
-    return get_lightness(ray, &scene)
+fn reflect(ray: &Ray, hit: &HitRecord) -> Option<(Ray, Color)> {
+    let sample = get_bounce_direction(ray.direction, hit)?;
+    // start off the side of the surface the ray leaves to, so that it does
+    // not hit the same surface again right away
+    let side = if sample.direction.dot(&hit.geometric_normal) < 0. { -1. } else { 1. };
+    let origin = hit.point + hit.geometric_normal * (side * SURFACE_OFFSET);
+    Some((Ray { origin, direction: sample.direction }, sample.weight))
 }
 
-fn reflect<T: Surface>(ray: &Ray, dist_to_surface: f32, surface: &T) -> (crate::Material, Ray) {
-    let hit_point = ray.origin + ray.direction * dist_to_surface;
-    let normal = surface.get_normal(hit_point);
-    let material = surface.get_material();
-    let bounce_direction =
-        get_bounce_direction(ray.direction, normal, material.material_kind);
-    let bounce_ray = Ray { origin: hit_point, direction: bounce_direction };
-    (material, bounce_ray)
+/// Direction of the bounced ray and the share of the light it carries,
+/// sampled from the BSDF of the material. `None` if the light is absorbed.
+fn get_bounce_direction(ray_direction: Vec3d, hit: &HitRecord) -> Option<Sample> {
+    let shading = Shading {
+        outgoing: ray_direction.normalize() * -1.,
+        normal: hit.shading_normal,
+        front_face: hit.front_face,
+    };
+    hit.material.sample_bsdf(hit, &shading)
 }
 
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
 
-// w = v - 2 * (v ∙ n) * n
-// TODO: encapsulate in material?
-fn get_bounce_direction(ray_direction: Vec3d, normal: Vec3d, material_kind: MaterialKind) -> Vec3d {
-    match material_kind {
-        MaterialKind::Diffuse => {
-            let mut bd = Vec3d::random_unit();
-            if normal * bd < 0. {
-                bd = bd * -1.;
-            }
-            bd + 0.5 * normal
-        },
-        MaterialKind::Glossy => {
-            let n = normal;
-            let v = ray_direction;
-            let w = v - 2. * v.dot(&n) * n;
-            w
-        }
+    use super::*;
+    use crate::primitives::vec3d::Point3d;
+    use crate::surface::material::{Material, MaterialKind};
+    use crate::surface::mesh::Mesh;
+    use crate::surface::triangle::Triangle;
+
+    // a closed tetrahedron with its faces wound outwards
+    fn tetrahedron(material: Material) -> Mesh {
+        let material = Arc::new(material);
+        let corners = [
+            Point3d::new(0., 0., 0.),
+            Point3d::new(1., 0., 0.),
+            Point3d::new(0., 1., 0.),
+            Point3d::new(0., 0., 1.),
+        ];
+        let faces = [[0, 2, 1], [0, 1, 3], [0, 3, 2], [1, 2, 3]];
+        let triangles = faces
+            .iter()
+            .map(|&[a, b, c]| Triangle::new(corners[a], corners[b], corners[c], material.clone()))
+            .collect();
+        Mesh::new(triangles)
+    }
+
+    #[test]
+    fn rays_enter_and_leave_a_dielectric_mesh() {
+        // without a change of index the ray goes straight through
+        let mut glass = Material::new(MaterialKind::Dielectric, Color::new(1., 1., 1.));
+        glass.ior = 1.;
+        glass.absorption = Color::new(1., 1., 1.);
+        let mesh = tetrahedron(glass);
+        let ray = Ray { origin: Point3d::new(0.2, 0.2, -1.), direction: Vec3d::new(0., 0., 1.) };
+
+        let entry = mesh.intersect(&ray).unwrap();
+        assert!(entry.front_face);
+        assert!(transmittance(&ray, &entry).red == 1.);
+
+        let (inside, _) = reflect(&ray, &entry).unwrap();
+        let exit = mesh.intersect(&inside).unwrap();
+        assert!(!exit.front_face);
+        // through the slanted face at z = 0.6
+        assert!((transmittance(&inside, &exit).red - f32::exp(-0.6)).abs() < 1e-3);
     }
-}
\ No newline at end of file
+}
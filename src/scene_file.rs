@@ -0,0 +1,813 @@
+//! A small line based scene description. Every block starts at the beginning
+//! of a line with a keyword and an optional argument, its properties follow
+//! on indented lines as a name and values separated by whitespace.
+//! Everything after `#` is a comment.
+//!
+//! ```text
+//! camera
+//!     location 0 -10 2
+//!     look_at 0 0 0
+//!     fov 40
+//! light
+//!     direction 1 1 1
+//! texture bricks
+//!     kind image
+//!     file bricks.png
+//! material red
+//!     kind diffuse
+//!     color 1 0.3 0.3
+//!     texture bricks
+//! sphere
+//!     center 0 0 0
+//!     radius 1
+//!     material red
+//! mesh monkey.obj
+//!     crease_angle 60
+//!     rotate z 90
+//!     translate 0 0 1
+//! ```
+
+use std::collections::HashMap;
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::camera::{ApertureShape, Camera, Projection, SensorFit};
+use crate::primitives::transform::Transform;
+use crate::primitives::vec3d::{Point3d, Vec3d};
+use crate::primitives::Color;
+use crate::scene::Scene;
+use crate::settings::{RenderSettings, View};
+use crate::surface::instance::Instance;
+use crate::surface::material::{Material, MaterialKind};
+use crate::surface::microfacet::Conductor;
+use crate::surface::mesh::Mesh;
+use crate::surface::sphere::Sphere;
+use crate::surface::Hittable;
+use crate::texture::Texture;
+use crate::texture::image::{Filter, ImageTexture, Wrap};
+use crate::texture::noise::Perlin;
+use crate::texture::procedural::{Mapping, Pattern, Procedural};
+
+pub struct SceneFile {
+    pub scene: Scene,
+    pub settings: RenderSettings,
+}
+
+#[derive(Debug, PartialEq)]
+pub struct SceneError {
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for SceneError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "line {}: {}", self.line, self.message)
+    }
+}
+
+type Result<T> = std::result::Result<T, SceneError>;
+
+fn error<T>(line: usize, message: impl Into<String>) -> Result<T> {
+    Err(SceneError { line, message: message.into() })
+}
+
+/// Reads a scene file. Mesh and image paths are relative to the file's directory.
+pub fn load(path: &Path) -> std::result::Result<SceneFile, String> {
+    let content = read_to_string(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+    let directory = path.parent().unwrap_or(Path::new(""));
+    parse(&content, directory).map_err(|e| format!("{}:{}: {}", path.display(), e.line, e.message))
+}
+
+pub fn parse(content: &str, directory: &Path) -> Result<SceneFile> {
+    let mut camera = None;
+    let mut light_source = None;
+    let mut settings = RenderSettings::default();
+    let mut textures: HashMap<&str, Arc<dyn Texture>> = HashMap::new();
+    let mut materials: HashMap<&str, Material> = HashMap::new();
+    let mut objects: Vec<Box<dyn Hittable>> = Vec::new();
+    // meshes are shared between instances with the same file and shading
+    let mut meshes: HashMap<(PathBuf, Option<u32>), Arc<dyn Hittable>> = HashMap::new();
+
+    for block in split_blocks(content)? {
+        match block.keyword {
+            "camera" => {
+                if camera.is_some() {
+                    return error(block.line, "The scene already has a camera")
+                }
+                camera = Some(parse_camera(&block)?);
+            }
+            "light" => {
+                if light_source.is_some() {
+                    return error(block.line, "Only one light source is supported")
+                }
+                light_source = Some(parse_light(&block)?);
+            }
+            "settings" => settings = parse_settings(&block)?,
+            "texture" => {
+                let name = block.argument()?;
+                let texture = parse_texture(&block, directory)?;
+                if textures.insert(name, texture).is_some() {
+                    return error(block.line, format!("Texture {name} is defined twice"))
+                }
+            }
+            "material" => {
+                let name = block.argument()?;
+                let material = parse_material(&block, &textures)?;
+                if materials.insert(name, material).is_some() {
+                    return error(block.line, format!("Material {name} is defined twice"))
+                }
+            }
+            "sphere" => objects.push(Box::new(parse_sphere(&block, &materials)?)),
+            "mesh" => {
+                let path = directory.join(block.argument()?);
+                let (crease_angle, transform) = parse_mesh(&block)?;
+                let key = (path, crease_angle.map(f32::to_bits));
+                let mesh = match meshes.get(&key) {
+                    Some(mesh) => mesh.clone(),
+                    None => {
+                        let mesh = Mesh::import(&key.0, crease_angle).or_else(|e| error(block.line, e.to_string()))?;
+                        meshes.entry(key).or_insert(Arc::new(mesh)).clone()
+                    }
+                };
+                objects.push(Box::new(Instance::new(mesh, transform)));
+            }
+            keyword => return error(block.line, format!("Unknown block {keyword}")),
+        }
+    }
+
+    let Some(camera) = camera else {
+        return error(content.lines().count(), "The scene has no camera")
+    };
+    let Some(light_source) = light_source else {
+        return error(content.lines().count(), "The scene has no light")
+    };
+    Ok(SceneFile {
+        scene: Scene::new(objects, camera, light_source),
+        settings,
+    })
+}
+
+struct Block<'a> {
+    line: usize,
+    keyword: &'a str,
+    arguments: Vec<&'a str>,
+    properties: Vec<Property<'a>>,
+}
+
+struct Property<'a> {
+    line: usize,
+    name: &'a str,
+    values: Vec<&'a str>,
+}
+
+impl<'a> Block<'a> {
+    fn argument(&self) -> Result<&'a str> {
+        if self.arguments.len() != 1 {
+            return error(self.line, format!("{} takes one argument", self.keyword))
+        }
+        Ok(self.arguments[0])
+    }
+
+    fn no_arguments(&self) -> Result<()> {
+        if !self.arguments.is_empty() {
+            return error(self.line, format!("{} takes no arguments", self.keyword))
+        }
+        Ok(())
+    }
+}
+
+impl<'a> Property<'a> {
+    fn count(&self, count: usize) -> Result<()> {
+        if self.values.len() != count {
+            return error(self.line, format!("{} takes {} value(s)", self.name, count))
+        }
+        Ok(())
+    }
+
+    fn word(&self) -> Result<&'a str> {
+        self.count(1)?;
+        Ok(self.values[0])
+    }
+
+    fn number<T: std::str::FromStr>(&self, index: usize) -> Result<T> {
+        let value = self.values[index];
+        value.parse().or_else(|_| error(self.line, format!("Invalid number {value} of {}", self.name)))
+    }
+
+    fn float(&self) -> Result<f32> {
+        self.count(1)?;
+        self.number(0)
+    }
+
+    /// A single number in [0, 1].
+    fn fraction(&self) -> Result<f32> {
+        let value = self.float()?;
+        if !(0. ..=1.).contains(&value) {
+            return error(self.line, format!("{} has to be between 0 and 1", self.name))
+        }
+        Ok(value)
+    }
+
+    /// A single finite number above 0.
+    fn positive(&self) -> Result<f32> {
+        let value = self.float()?;
+        if !value.is_finite() || value <= 0. {
+            return error(self.line, format!("{} has to be positive", self.name))
+        }
+        Ok(value)
+    }
+
+    fn vector(&self) -> Result<Vec3d> {
+        self.count(3)?;
+        Ok(Vec3d::new(self.number(0)?, self.number(1)?, self.number(2)?))
+    }
+
+    fn color(&self) -> Result<Color> {
+        let v = self.vector()?;
+        Ok(Color::new(v.x, v.y, v.z))
+    }
+
+    fn unknown<T>(&self, block: &Block) -> Result<T> {
+        error(self.line, format!("Unknown property {} of {}", self.name, block.keyword))
+    }
+}
+
+fn split_blocks(content: &str) -> Result<Vec<Block<'_>>> {
+    let mut blocks: Vec<Block> = Vec::new();
+    for (index, line) in content.lines().enumerate() {
+        let number = index + 1;
+        let text = line.split('#').next().unwrap_or_default();
+        let mut words = text.split_whitespace();
+        let Some(first) = words.next() else {
+            continue
+        };
+        if text.starts_with(char::is_whitespace) {
+            let Some(block) = blocks.last_mut() else {
+                return error(number, "A property has to belong to a block")
+            };
+            block.properties.push(Property { line: number, name: first, values: words.collect() });
+        } else {
+            blocks.push(Block { line: number, keyword: first, arguments: words.collect(), properties: Vec::new() });
+        }
+    }
+    Ok(blocks)
+}
+
+fn required<T>(value: Option<T>, block: &Block, name: &str) -> Result<T> {
+    match value {
+        Some(value) => Ok(value),
+        None => error(block.line, format!("{} needs a {}", block.keyword, name)),
+    }
+}
+
+fn parse_camera(block: &Block) -> Result<Camera> {
+    block.no_arguments()?;
+    let mut location = None;
+    let mut target = None;
+    let mut up = Vec3d::new(0., 0., 1.);
+    let mut options = Vec::new();
+    for property in &block.properties {
+        match property.name {
+            "location" => location = Some(property.vector()?),
+            "look_at" => target = Some(property.vector()?),
+            "up" => up = property.vector()?,
+            _ => options.push(property),
+        }
+    }
+    let location = required(location, block, "location")?;
+    let target = required(target, block, "look_at")?;
+    if (target - location).len() == 0. {
+        return error(block.line, "The camera can't look at its own location")
+    }
+
+    let mut camera = Camera::look_at(location, target, up);
+    for property in options {
+        match property.name {
+            "fov" => {
+                camera.fov = property.float()?;
+                if !(camera.fov > 0. && camera.fov < 180.) {
+                    return error(property.line, "The field of view has to be between 0 and 180 degrees")
+                }
+            }
+            "ortho_scale" => camera.ortho_scale = property.positive()?,
+            "focus_distance" => camera.focus_distance = property.positive()?,
+            "aperture" => {
+                camera.aperture_radius = property.float()?;
+                if !camera.aperture_radius.is_finite() || camera.aperture_radius < 0. {
+                    return error(property.line, "The aperture can't be negative")
+                }
+            }
+            "aperture_blades" => {
+                property.count(1)?;
+                camera.aperture_shape = match property.number(0)? {
+                    0 => ApertureShape::Circular,
+                    blades => ApertureShape::Polygonal { blades },
+                };
+            }
+            "projection" => {
+                camera.projection = match property.word()? {
+                    "perspective" => Projection::Perspective,
+                    "orthographic" => Projection::Orthographic,
+                    "equirectangular" => Projection::Equirectangular,
+                    other => return error(property.line, format!("Unknown projection {other}")),
+                };
+            }
+            "sensor_fit" => {
+                camera.sensor_fit = match property.word()? {
+                    "auto" => SensorFit::Auto,
+                    "horizontal" => SensorFit::Horizontal,
+                    "vertical" => SensorFit::Vertical,
+                    other => return error(property.line, format!("Unknown sensor fit {other}")),
+                };
+            }
+            _ => return property.unknown(block),
+        }
+    }
+    Ok(camera)
+}
+
+fn parse_light(block: &Block) -> Result<Vec3d> {
+    block.no_arguments()?;
+    let mut direction = None;
+    for property in &block.properties {
+        match property.name {
+            "direction" => direction = Some(property.vector()?),
+            _ => return property.unknown(block),
+        }
+    }
+    let direction = required(direction, block, "direction")?;
+    if direction.len() == 0. {
+        return error(block.line, "The light direction can't be zero")
+    }
+    Ok(direction.normalize())
+}
+
+fn parse_settings(block: &Block) -> Result<RenderSettings> {
+    block.no_arguments()?;
+    let mut settings = RenderSettings::default();
+    for property in &block.properties {
+        match property.name {
+            "samples_per_pixel" => {
+                property.count(1)?;
+                settings.samples_per_pixel = property.number(0)?;
+                if !(1..=RenderSettings::MAX_SAMPLES_PER_PIXEL).contains(&settings.samples_per_pixel) {
+                    return error(property.line, format!(
+                        "Samples per pixel have to be between 1 and {}", RenderSettings::MAX_SAMPLES_PER_PIXEL
+                    ))
+                }
+            }
+            "max_depth" => {
+                property.count(1)?;
+                settings.max_depth = property.number(0)?;
+                if settings.max_depth > RenderSettings::MAX_DEPTH {
+                    return error(property.line, format!("Max depth can't exceed {}", RenderSettings::MAX_DEPTH))
+                }
+            }
+            "view" => {
+                settings.view = match property.word()? {
+                    "shaded" => View::Shaded,
+                    "normals" => View::Normals,
+                    "uv" => View::Uv,
+                    "barycentrics" => View::Barycentrics,
+                    "primitives" => View::Primitives,
+                    other => return error(property.line, format!("Unknown view {other}")),
+                };
+            }
+            _ => return property.unknown(block),
+        }
+    }
+    Ok(settings)
+}
+
+fn parse_texture(block: &Block, directory: &Path) -> Result<Arc<dyn Texture>> {
+    let mut kind = None;
+    let mut options = Vec::new();
+    for property in &block.properties {
+        match property.name {
+            "kind" => kind = Some(property.word()?),
+            _ => options.push(property),
+        }
+    }
+    match required(kind, block, "kind")? {
+        "image" => {
+            let mut file = None;
+            let mut filter = Filter::Bilinear;
+            let mut wrap = Wrap::Repeat;
+            for property in options {
+                match property.name {
+                    "file" => file = Some((directory.join(property.word()?), property.line)),
+                    "filter" => {
+                        filter = match property.word()? {
+                            "nearest" => Filter::Nearest,
+                            "bilinear" => Filter::Bilinear,
+                            other => return error(property.line, format!("Unknown filter {other}")),
+                        };
+                    }
+                    "wrap" => {
+                        wrap = match property.word()? {
+                            "repeat" => Wrap::Repeat,
+                            "clamp" => Wrap::Clamp,
+                            other => return error(property.line, format!("Unknown wrap mode {other}")),
+                        };
+                    }
+                    _ => return property.unknown(block),
+                }
+            }
+            let (path, line) = required(file, block, "file")?;
+            let mut texture = ImageTexture::load(&path).or_else(|e| error(line, e))?;
+            texture.filter = filter;
+            texture.wrap = wrap;
+            Ok(Arc::new(texture))
+        }
+        kind => {
+            let pattern = match kind {
+                "checker" => Pattern::Checker,
+                "grid" => Pattern::Grid { line_width: 0.05 },
+                "gradient" => Pattern::Gradient { axis: Vec3d::new(0., 0., 1.) },
+                "noise" => Pattern::Noise { octaves: 1 },
+                "turbulence" => Pattern::Turbulence { octaves: 4 },
+                "marble" => Pattern::Marble { octaves: 4, strength: 5. },
+                "wood" => Pattern::Wood { strength: 1. },
+                other => return error(block.line, format!("Unknown texture kind {other}")),
+            };
+            parse_procedural(block, pattern, options)
+        }
+    }
+}
+
+/// A procedural texture, its pattern holds the defaults of its kind.
+fn parse_procedural(block: &Block, pattern: Pattern, options: Vec<&Property>) -> Result<Arc<dyn Texture>> {
+    let mut texture = Procedural::new(pattern);
+    let mut seed = 0;
+    for property in options {
+        match (property.name, &mut texture.pattern) {
+            ("mapping", _) => {
+                texture.mapping = match property.word()? {
+                    "point" => Mapping::Point,
+                    "uv" => Mapping::Uv,
+                    other => return error(property.line, format!("Unknown mapping {other}")),
+                };
+            }
+            ("scale", _) => {
+                texture.scale = property.float()?;
+                if !texture.scale.is_finite() || texture.scale == 0. {
+                    return error(property.line, "The scale has to be finite and not zero")
+                }
+            }
+            ("primary", _) => texture.primary = property.color()?,
+            ("secondary", _) => texture.secondary = property.color()?,
+            ("seed", _) => {
+                property.count(1)?;
+                seed = property.number(0)?;
+            }
+            ("line_width", Pattern::Grid { line_width }) => *line_width = property.positive()?,
+            ("axis", Pattern::Gradient { axis }) => {
+                *axis = property.vector()?;
+                if !(axis.len_squared() > 0. && axis.len_squared().is_finite()) {
+                    return error(property.line, "The gradient axis can't be zero")
+                }
+            }
+            (
+                "octaves",
+                Pattern::Noise { octaves } | Pattern::Turbulence { octaves } | Pattern::Marble { octaves, .. },
+            ) => {
+                property.count(1)?;
+                *octaves = property.number(0)?;
+                if !(1..=Perlin::MAX_OCTAVES).contains(octaves) {
+                    return error(property.line, format!("Octaves have to be between 1 and {}", Perlin::MAX_OCTAVES))
+                }
+            }
+            ("strength", Pattern::Marble { strength, .. } | Pattern::Wood { strength }) => {
+                *strength = property.positive()?
+            }
+            _ => return property.unknown(block),
+        }
+    }
+    Ok(Arc::new(texture.with_seed(seed)))
+}
+
+fn parse_material(block: &Block, textures: &HashMap<&str, Arc<dyn Texture>>) -> Result<Material> {
+    let mut material_kind = None;
+    let mut color = None;
+    let mut options = Vec::new();
+    for property in &block.properties {
+        match property.name {
+            "kind" => {
+                material_kind = Some(match property.word()? {
+                    "diffuse" => MaterialKind::Diffuse,
+                    "glossy" => MaterialKind::Glossy,
+                    "plastic" => MaterialKind::Plastic,
+                    "dielectric" => MaterialKind::Dielectric,
+                    "metal" => MaterialKind::Metal,
+                    "principled" => MaterialKind::Principled,
+                    other => return error(property.line, format!("Unknown material kind {other}")),
+                });
+            }
+            "color" => color = Some(property.color()?),
+            _ => options.push(property),
+        }
+    }
+    let material_kind = required(material_kind, block, "kind")?;
+    let mut material = Material::new(material_kind, required(color, block, "color")?);
+    // the principled model has a scalar specular level rather than a color
+    let principled = material_kind == MaterialKind::Principled;
+    for property in options {
+        match property.name {
+            "specular" if principled => material.principled.specular = property.fraction()?,
+            "specular_tint" if principled => material.principled.specular_tint = property.fraction()?,
+            "metallic" if principled => material.principled.metallic = property.fraction()?,
+            "sheen" if principled => material.principled.sheen = property.fraction()?,
+            "sheen_tint" if principled => material.principled.sheen_tint = property.fraction()?,
+            "clearcoat" if principled => material.principled.clearcoat = property.fraction()?,
+            "clearcoat_roughness" if principled => material.principled.clearcoat_roughness = property.fraction()?,
+            "transmission" if principled => material.principled.transmission = property.fraction()?,
+            "specular" => material.specular = property.color()?,
+            "roughness" => material.roughness = property.fraction()?,
+            "conductor" => material.conductor = parse_conductor(property)?,
+            "ior" => material.ior = property.positive()?,
+            "absorption" => material.absorption = property.color()?,
+            "dissolve" => material.dissolve = property.fraction()?,
+            "emission" => material.emission = property.color()?,
+            "texture" => {
+                let name = property.word()?;
+                let Some(found) = textures.get(name) else {
+                    return error(property.line, format!("Unknown texture {name}"))
+                };
+                material.texture = Some(found.clone());
+            }
+            _ => return property.unknown(block),
+        }
+    }
+    Ok(material)
+}
+
+/// A metal by name or as the real and imaginary parts of its index of refraction.
+fn parse_conductor(property: &Property) -> Result<Conductor> {
+    if property.values.len() == 6 {
+        let channels: Vec<f32> = (0..6).map(|i| property.number(i)).collect::<Result<_>>()?;
+        return Ok(Conductor {
+            eta: Color::new(channels[0], channels[1], channels[2]),
+            k: Color::new(channels[3], channels[4], channels[5]),
+        })
+    }
+    match property.word()? {
+        "aluminium" => Ok(Conductor::ALUMINIUM),
+        "copper" => Ok(Conductor::COPPER),
+        "gold" => Ok(Conductor::GOLD),
+        "silver" => Ok(Conductor::SILVER),
+        other => error(property.line, format!("Unknown conductor {other}")),
+    }
+}
+
+fn parse_sphere(block: &Block, materials: &HashMap<&str, Material>) -> Result<Sphere> {
+    block.no_arguments()?;
+    let mut center = None;
+    let mut radius = None;
+    let mut material = None;
+    for property in &block.properties {
+        match property.name {
+            "center" => center = Some(property.vector()?),
+            "radius" => {
+                let value = property.float()?;
+                if !value.is_finite() || value <= 0. {
+                    return error(property.line, "The radius has to be positive")
+                }
+                radius = Some(value);
+            }
+            "material" => {
+                let name = property.word()?;
+                let Some(found) = materials.get(name) else {
+                    return error(property.line, format!("Unknown material {name}"))
+                };
+                material = Some(found.clone());
+            }
+            _ => return property.unknown(block),
+        }
+    }
+    let center: Point3d = required(center, block, "center")?;
+    let radius = required(radius, block, "radius")?;
+    let material = required(material, block, "material")?;
+    Ok(Sphere::new(center, radius, material))
+}
+
+/// The crease angle and the transform of a mesh. Transform properties are
+/// applied in the order they are written.
+fn parse_mesh(block: &Block) -> Result<(Option<f32>, Transform)> {
+    let mut crease_angle = None;
+    let mut transform = Transform::identity();
+    for property in &block.properties {
+        let step = match property.name {
+            "crease_angle" => {
+                crease_angle = Some(property.float()?);
+                continue
+            }
+            "translate" => Transform::translate(property.vector()?),
+            "scale" => {
+                let v = match property.values.len() {
+                    1 => {
+                        let factor = property.number(0)?;
+                        Vec3d::new(factor, factor, factor)
+                    }
+                    _ => property.vector()?,
+                };
+                if [v.x, v.y, v.z].iter().any(|factor| !factor.is_finite() || *factor == 0.) {
+                    return error(property.line, "Scale factors have to be finite and not zero")
+                }
+                Transform::scale(v.x, v.y, v.z)
+            }
+            "rotate" => {
+                // either a named axis or a vector, followed by the angle in degrees
+                let (axis, angle) = match property.values.len() {
+                    2 => {
+                        let axis = match property.values[0] {
+                            "x" => Vec3d::new(1., 0., 0.),
+                            "y" => Vec3d::new(0., 1., 0.),
+                            "z" => Vec3d::new(0., 0., 1.),
+                            other => return error(property.line, format!("Unknown axis {other}")),
+                        };
+                        (axis, property.number::<f32>(1)?)
+                    }
+                    4 => {
+                        let axis = Vec3d::new(property.number(0)?, property.number(1)?, property.number(2)?);
+                        (axis, property.number::<f32>(3)?)
+                    }
+                    _ => return error(property.line, "rotate takes an axis and an angle"),
+                };
+                if axis.len() == 0. {
+                    return error(property.line, "The rotation axis can't be zero")
+                }
+                Transform::rotate(axis.normalize(), angle.to_radians())
+            }
+            _ => return property.unknown(block),
+        };
+        transform = step * transform;
+    }
+    Ok((crease_angle, transform))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SCENE: &str = "
+# two spheres
+camera
+    location 0 -10 0
+    look_at 0 0 0
+    fov 40
+    projection orthographic
+
+light
+    direction 0 0 2
+
+settings
+    samples_per_pixel 4 # comment after a value
+    max_depth 3
+
+material red
+    kind diffuse
+    color 1 0 0
+
+sphere
+    center 0 0 0
+    radius 1
+    material red
+sphere
+    center 0 0 -101
+    radius 100
+    material red
+";
+
+    fn parse_error(content: &str) -> SceneError {
+        match parse(content, Path::new("")) {
+            Ok(_) => panic!("parsing should have failed"),
+            Err(e) => e,
+        }
+    }
+
+    #[test]
+    fn parses_scene() {
+        let file = parse(SCENE, Path::new("")).unwrap();
+
+        // both spheres are in the world
+        let bounds = file.scene.world.bounding_box();
+        assert!(bounds.min.z == -201. && bounds.max.z == 1.);
+        assert!(file.scene.camera.fov == 40.);
+        assert!(file.scene.camera.projection == Projection::Orthographic);
+        assert!(file.scene.light_source == Vec3d::new(0., 0., 1.));
+        assert!(file.settings.samples_per_pixel == 4 && file.settings.max_depth == 3);
+    }
+
+    #[test]
+    fn errors_point_at_lines() {
+        let unknown_material = SCENE.replace("    material red\nsphere", "    material blue\nsphere");
+        assert!(parse_error(&unknown_material).line == 23);
+
+        let bad_number = SCENE.replace("fov 40", "fov wide");
+        assert!(parse_error(&bad_number).line == 6);
+
+        let unknown_block = format!("{SCENE}plane\n");
+        assert!(parse_error(&unknown_block).line == 28);
+
+        let missing_radius = SCENE.replace("    radius 100\n", "");
+        assert!(parse_error(&missing_radius).line == 24);
+
+        let unknown_texture = SCENE.replace("color 1 0 0", "color 1 0 0\n    texture bricks");
+        assert!(parse_error(&unknown_texture).line == 19);
+    }
+
+    #[test]
+    fn values_out_of_range() {
+        assert!(parse_error(&SCENE.replace("fov 40", "fov 180")).line == 6);
+        assert!(parse_error(&SCENE.replace("fov 40", "fov 0")).line == 6);
+        assert!(parse_error(&SCENE.replace("fov 40", "fov 40\n    aperture -1")).line == 7);
+        assert!(parse_error(&SCENE.replace("radius 100", "radius 0")).line == 26);
+        for option in ["ortho_scale 0", "ortho_scale inf", "focus_distance -1", "focus_distance nan"] {
+            assert!(parse_error(&SCENE.replace("fov 40", &format!("fov 40\n    {option}"))).line == 7);
+        }
+
+        for scale in ["scale 0", "scale nan", "scale 1 inf 1"] {
+            let content = format!("mesh a.obj\n    translate 1 0 0\n    {scale}\n");
+            let blocks = split_blocks(&content).unwrap();
+            assert!(parse_mesh(&blocks[0]).err().unwrap().line == 3);
+        }
+    }
+
+    #[test]
+    fn property_without_block() {
+        assert!(parse_error("  fov 40\ncamera").line == 1);
+    }
+
+    #[test]
+    fn principled_parameters_are_fractions() {
+        let content = "material coat\n    kind principled\n    color 1 1 1\n    metallic 1\n    clearcoat 0.5\n";
+        let blocks = split_blocks(content).unwrap();
+        assert!(parse_material(&blocks[0], &HashMap::new()).is_ok());
+
+        for (from, to, line) in [("metallic 1", "metallic 1.5", 4), ("clearcoat 0.5", "clearcoat -0.5", 5)] {
+            let invalid = content.replace(from, to);
+            let blocks = split_blocks(&invalid).unwrap();
+            assert!(parse_material(&blocks[0], &HashMap::new()).err().unwrap().line == line);
+        }
+    }
+
+    #[test]
+    fn material_properties_in_range() {
+        let content = "material glass\n    kind dielectric\n    color 1 1 1\n    ior 1.5\n    roughness 0.1\n    dissolve 1\n";
+        let blocks = split_blocks(content).unwrap();
+        assert!(parse_material(&blocks[0], &HashMap::new()).is_ok());
+
+        for (from, to, line) in [
+            ("ior 1.5", "ior 0", 4),
+            ("ior 1.5", "ior -1.5", 4),
+            ("roughness 0.1", "roughness 2", 5),
+            ("roughness 0.1", "roughness nan", 5),
+            ("dissolve 1", "dissolve -0.5", 6),
+        ] {
+            let invalid = content.replace(from, to);
+            let blocks = split_blocks(&invalid).unwrap();
+            assert!(parse_material(&blocks[0], &HashMap::new()).err().unwrap().line == line);
+        }
+    }
+
+    #[test]
+    fn procedural_texture_properties() {
+        let content = "texture marble\n    kind marble\n    octaves 6\n    scale 0.5\n    primary 1 1 1\n";
+        let blocks = split_blocks(content).unwrap();
+        assert!(parse_texture(&blocks[0], Path::new("")).is_ok());
+
+        let other_kind = content.replace("kind marble", "kind checker");
+        let blocks = split_blocks(&other_kind).unwrap();
+        assert!(parse_texture(&blocks[0], Path::new("")).err().unwrap().line == 3);
+
+        for (from, to, line) in [
+            ("octaves 6", "octaves 4000000000", 3),
+            ("octaves 6", "octaves 0", 3),
+            ("scale 0.5", "scale 0", 4),
+            ("scale 0.5", "scale inf", 4),
+        ] {
+            let invalid = content.replace(from, to);
+            let blocks = split_blocks(&invalid).unwrap();
+            assert!(parse_texture(&blocks[0], Path::new("")).err().unwrap().line == line);
+        }
+        let blocks = split_blocks("texture ramp\n    kind gradient\n    axis 0 0 0\n").unwrap();
+        assert!(parse_texture(&blocks[0], Path::new("")).err().unwrap().line == 3);
+        let blocks = split_blocks("texture tiles\n    kind grid\n    line_width 0\n").unwrap();
+        assert!(parse_texture(&blocks[0], Path::new("")).err().unwrap().line == 3);
+        let invalid = content.replace("octaves 6", "strength -5");
+        let blocks = split_blocks(&invalid).unwrap();
+        assert!(parse_texture(&blocks[0], Path::new("")).err().unwrap().line == 3);
+    }
+
+    #[test]
+    fn transforms_apply_in_order() {
+        let content = "mesh a.obj\n    translate 1 0 0\n    rotate z 90\n";
+        let blocks = split_blocks(content).unwrap();
+        let (_, transform) = parse_mesh(&blocks[0]).unwrap();
+
+        let point = transform.apply_point(Point3d::new(0., 0., 0.));
+        assert!((point - Point3d::new(0., 1., 0.)).len() < 1e-5);
+    }
+}
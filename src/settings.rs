@@ -0,0 +1,77 @@
+use std::fmt;
+
+/// What the camera rays show. Everything but `Shaded` is a debugging aid
+/// that looks only at the first hit.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum View {
+    Shaded,
+    Normals,
+    Uv,
+    Barycentrics,
+    Primitives,
+}
+
+impl View {
+    pub fn next(&self) -> View {
+        match self {
+            View::Shaded => View::Normals,
+            View::Normals => View::Uv,
+            View::Uv => View::Barycentrics,
+            View::Barycentrics => View::Primitives,
+            View::Primitives => View::Shaded,
+        }
+    }
+}
+
+impl fmt::Display for View {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            View::Shaded => write!(f, "shaded"),
+            View::Normals => write!(f, "normals"),
+            View::Uv => write!(f, "uv"),
+            View::Barycentrics => write!(f, "barycentrics"),
+            View::Primitives => write!(f, "primitives"),
+        }
+    }
+}
+
+/// Quality knobs of a render, adjustable while the window is open.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct RenderSettings {
+    /// Camera rays traced per pixel and frame.
+    pub samples_per_pixel: u32,
+    /// How many times a ray may bounce before the light is gathered.
+    pub max_depth: u8,
+    pub view: View,
+}
+
+impl RenderSettings {
+    pub const MAX_SAMPLES_PER_PIXEL: u32 = 256;
+    pub const MAX_DEPTH: u8 = 32;
+
+    pub fn increase_samples(&mut self) {
+        self.samples_per_pixel = u32::min(self.samples_per_pixel * 2, Self::MAX_SAMPLES_PER_PIXEL);
+    }
+
+    pub fn decrease_samples(&mut self) {
+        self.samples_per_pixel = u32::max(self.samples_per_pixel / 2, 1);
+    }
+
+    pub fn increase_depth(&mut self) {
+        self.max_depth = u8::min(self.max_depth + 1, Self::MAX_DEPTH);
+    }
+
+    pub fn decrease_depth(&mut self) {
+        self.max_depth = self.max_depth.saturating_sub(1);
+    }
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        RenderSettings {
+            samples_per_pixel: 1,
+            max_depth: 2,
+            view: View::Shaded,
+        }
+    }
+}
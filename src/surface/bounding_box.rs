@@ -0,0 +1,115 @@
+use crate::primitives::Ray;
+use crate::primitives::vec3d::{Point3d, Vec3d};
+
+use std::mem::swap;
+
+#[derive(Debug, Clone, Copy)]
+pub struct BoundingBox {
+    pub(crate) min: Point3d,
+    pub(crate) max: Point3d,
+}
+
+impl BoundingBox {
+    /// An inverted box that any point or box grows into.
+    pub fn empty() -> BoundingBox {
+        BoundingBox {
+            min: Point3d::new(f32::MAX, f32::MAX, f32::MAX),
+            max: Point3d::new(f32::MIN, f32::MIN, f32::MIN),
+        }
+    }
+
+    pub fn grow(&mut self, point: Point3d) {
+        self.min = self.min.min(&point);
+        self.max = self.max.max(&point);
+    }
+
+    pub fn union(&self, other: &BoundingBox) -> BoundingBox {
+        BoundingBox {
+            min: self.min.min(&other.min),
+            max: self.max.max(&other.max),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.min.x > self.max.x || self.min.y > self.max.y || self.min.z > self.max.z
+    }
+
+    pub fn centroid(&self) -> Point3d {
+        (self.min + self.max) * 0.5
+    }
+
+    pub fn extent(&self) -> Vec3d {
+        self.max - self.min
+    }
+
+    pub fn surface_area(&self) -> f32 {
+        if self.is_empty() {
+            return 0.
+        }
+        let e = self.extent();
+        2. * (e.x * e.y + e.y * e.z + e.z * e.x)
+    }
+
+    /// Slab test. Returns the distance at which the ray enters the box,
+    /// or `None` if the box is missed or lies beyond `t_max`.
+    pub fn intersect(&self, ray: &Ray, t_max: f32) -> Option<f32> {
+        // the slabs of an inverted box would swap into an infinite one
+        if self.is_empty() {
+            return None
+        }
+        let mut min_t = f32::MIN;
+        let mut max_t = t_max;
+        let hit = intersect_one_dimention(
+            &mut min_t,
+            &mut max_t,
+            ray.direction.x,
+            self.min.x,
+            self.max.x,
+            ray.origin.x,
+        ) && intersect_one_dimention(
+            &mut min_t,
+            &mut max_t,
+            ray.direction.y,
+            self.min.y,
+            self.max.y,
+            ray.origin.y,
+        ) && intersect_one_dimention(
+            &mut min_t,
+            &mut max_t,
+            ray.direction.z,
+            self.min.z,
+            self.max.z,
+            ray.origin.z,
+        ) && max_t > 0.;
+        if hit {
+            Some(f32::max(min_t, 0.))
+        } else {
+            None
+        }
+    }
+}
+
+fn intersect_one_dimention(
+    min_t: &mut f32,
+    max_t: &mut f32,
+    direction_i: f32,
+    min_i: f32,
+    max_i: f32,
+    origin_i: f32,
+) -> bool {
+    if direction_i != 0. {
+        let min_i_aligned = min_i - origin_i;
+        let max_i_aligned = max_i - origin_i;
+        let direction_i_inv = 1. / direction_i;
+        let mut new_min_t = min_i_aligned * direction_i_inv;
+        let mut new_max_t = max_i_aligned * direction_i_inv;
+        if new_min_t > new_max_t {
+            swap(&mut new_min_t, &mut new_max_t)
+        };
+        *min_t = f32::max(*min_t, new_min_t);
+        *max_t = f32::min(*max_t, new_max_t);
+        return max_t >= min_t;
+    } else {
+        return (min_i..=max_i).contains(&origin_i);
+    }
+}
@@ -0,0 +1,445 @@
+//! Scattering at a surface. Every material builds a BSDF at a hit, which the
+//! path tracer samples for the direction to continue in.
+
+use std::f32::consts::PI;
+
+use crate::primitives::rand;
+use crate::primitives::vec3d::Vec3d;
+use crate::primitives::Color;
+
+use super::microfacet::{fresnel_schlick, Ggx};
+
+/// The geometry of a hit, all vectors have unit length.
+pub struct Shading {
+    /// Towards where the light goes, back along the ray.
+    pub outgoing: Vec3d,
+    /// Shading normal on the side of `outgoing`.
+    pub normal: Vec3d,
+    /// Whether the outside of the surface was hit.
+    pub front_face: bool,
+}
+
+pub struct Sample {
+    /// Unit direction the light comes from.
+    pub direction: Vec3d,
+    /// BSDF times the cosine to the normal over the density of the direction.
+    pub weight: Color,
+}
+
+pub trait Bsdf {
+    /// Importance samples the direction light comes from, `None` if the path ends.
+    fn sample(&self, shading: &Shading) -> Option<Sample>;
+
+    /// BSDF times the cosine of `incoming` to the normal. Perfectly specular
+    /// scattering is a delta function and evaluates to black.
+    fn evaluate(&self, shading: &Shading, incoming: Vec3d) -> Color;
+
+    /// Density over solid angle with which `sample` picks `incoming`,
+    /// zero for perfectly specular scattering.
+    fn pdf(&self, shading: &Shading, incoming: Vec3d) -> f32;
+}
+
+const BLACK: Color = Color { red: 0., green: 0., blue: 0. };
+const WHITE: Color = Color { red: 1., green: 1., blue: 1. };
+
+/// Ideal diffuse reflection.
+pub struct Lambertian {
+    pub albedo: Color,
+}
+
+impl Bsdf for Lambertian {
+    fn sample(&self, shading: &Shading) -> Option<Sample> {
+        // cosine weighted, so that the weight is just the albedo
+        let direction = shading.normal + Vec3d::random_unit();
+        // the random vector may just cancel the normal out
+        let direction = if direction.len_squared() < 1e-8 { shading.normal } else { direction.normalize() };
+        Some(Sample { direction, weight: self.albedo })
+    }
+
+    fn evaluate(&self, shading: &Shading, incoming: Vec3d) -> Color {
+        self.albedo * (incoming.dot(&shading.normal).max(0.) / PI)
+    }
+
+    fn pdf(&self, shading: &Shading, incoming: Vec3d) -> f32 {
+        incoming.dot(&shading.normal).max(0.) / PI
+    }
+}
+
+/// GGX reflection with a Fresnel term of the cosine between the
+/// directions and the microfacet normal.
+pub struct MicrofacetReflection<F: Fn(f32) -> Color> {
+    pub ggx: Ggx,
+    pub fresnel: F,
+}
+
+impl<F: Fn(f32) -> Color> Bsdf for MicrofacetReflection<F> {
+    fn sample(&self, shading: &Shading) -> Option<Sample> {
+        // a reflection into the surface is lost
+        let reflection = self.ggx.sample_reflection(shading.outgoing, shading.normal)?;
+        Some(Sample {
+            direction: reflection.direction,
+            weight: (self.fresnel)(reflection.cos_half) * reflection.shadowing,
+        })
+    }
+
+    fn evaluate(&self, shading: &Shading, incoming: Vec3d) -> Color {
+        match self.ggx.evaluate(shading.outgoing, incoming, shading.normal) {
+            Some((cos_half, value, _)) => (self.fresnel)(cos_half) * value,
+            None => BLACK,
+        }
+    }
+
+    fn pdf(&self, shading: &Shading, incoming: Vec3d) -> f32 {
+        self.ggx.evaluate(shading.outgoing, incoming, shading.normal).map_or(0., |(_, _, pdf)| pdf)
+    }
+}
+
+/// A diffuse base under a glossy highlight, sampled by their brightness.
+pub struct Plastic<F: Fn(f32) -> Color> {
+    pub diffuse: Lambertian,
+    pub specular: MicrofacetReflection<F>,
+    /// Brightness of the highlight.
+    pub specular_weight: f32,
+}
+
+impl<F: Fn(f32) -> Color> Plastic<F> {
+    /// The probability of sampling the highlight and the scale that keeps
+    /// both layers together from reflecting more than 100%.
+    fn layers(&self) -> Option<(f32, f32)> {
+        let total = self.diffuse.albedo.luminance() + self.specular_weight;
+        if total <= 0. {
+            return None
+        }
+        Some((self.specular_weight / total, f32::min(total, 1.) / total))
+    }
+}
+
+impl<F: Fn(f32) -> Color> Bsdf for Plastic<F> {
+    fn sample(&self, shading: &Shading) -> Option<Sample> {
+        let (specular_probability, scale) = self.layers()?;
+        let (sample, probability) = if rand::get_random_float() < specular_probability {
+            (self.specular.sample(shading)?, specular_probability)
+        } else {
+            (self.diffuse.sample(shading)?, 1. - specular_probability)
+        };
+        Some(Sample { direction: sample.direction, weight: sample.weight * (scale / probability) })
+    }
+
+    fn evaluate(&self, shading: &Shading, incoming: Vec3d) -> Color {
+        let Some((_, scale)) = self.layers() else {
+            return BLACK
+        };
+        (self.diffuse.evaluate(shading, incoming) + self.specular.evaluate(shading, incoming)) * scale
+    }
+
+    fn pdf(&self, shading: &Shading, incoming: Vec3d) -> f32 {
+        let Some((specular_probability, _)) = self.layers() else {
+            return 0.
+        };
+        specular_probability * self.specular.pdf(shading, incoming)
+            + (1. - specular_probability) * self.diffuse.pdf(shading, incoming)
+    }
+}
+
+/// Smooth boundary of glass or water, reflecting or refracting by the
+/// Fresnel equations. `tint` colors the transmitted light.
+pub struct SmoothDielectric {
+    pub ior: f32,
+    pub tint: Color,
+}
+
+impl Bsdf for SmoothDielectric {
+    fn sample(&self, shading: &Shading) -> Option<Sample> {
+        let direction = shading.outgoing * -1.;
+        let cos_incident = shading.outgoing.dot(&shading.normal).clamp(0., 1.);
+        // entering the medium from the outside or leaving it
+        let eta = if shading.front_face { 1. / self.ior } else { self.ior };
+        // reflect with the Fresnel probability, always on total internal reflection
+        if rand::get_random_float() < fresnel_dielectric(cos_incident, eta) {
+            Some(Sample { direction: mirror_direction(direction, shading.normal), weight: WHITE })
+        } else {
+            let refracted = refract_direction(direction, shading.normal, cos_incident, eta);
+            Some(Sample { direction: refracted, weight: self.tint })
+        }
+    }
+
+    fn evaluate(&self, _shading: &Shading, _incoming: Vec3d) -> Color {
+        BLACK
+    }
+
+    fn pdf(&self, _shading: &Shading, _incoming: Vec3d) -> f32 {
+        0.
+    }
+}
+
+/// Parameters of the Disney principled BSDF, in [0, 1] apart from the
+/// base color, roughness and index of refraction of the material.
+#[derive(Debug, Clone, Copy)]
+pub struct Principled {
+    /// Blends from a dielectric to a metal with the base color as reflectance.
+    pub metallic: f32,
+    /// Head on reflectance of the dielectric part, 0.5 is 4%.
+    pub specular: f32,
+    /// Tints the dielectric highlight towards the base color.
+    pub specular_tint: f32,
+    /// Extra reflection at grazing angles, for cloth.
+    pub sheen: f32,
+    pub sheen_tint: f32,
+    /// Strength of a second, colorless highlight as of a varnish.
+    pub clearcoat: f32,
+    pub clearcoat_roughness: f32,
+    /// Blends the dielectric part from diffuse to smooth glass.
+    pub transmission: f32,
+}
+
+impl Default for Principled {
+    fn default() -> Self {
+        Principled {
+            metallic: 0.,
+            specular: 0.5,
+            specular_tint: 0.,
+            sheen: 0.,
+            sheen_tint: 0.5,
+            clearcoat: 0.,
+            clearcoat_roughness: 0.03,
+            transmission: 0.,
+        }
+    }
+}
+
+/// A principled material at a hit. The clearcoat uses GGX rather than
+/// the GTR1 distribution of the original model.
+pub struct PrincipledBsdf<'a> {
+    pub parameters: &'a Principled,
+    pub base_color: Color,
+    pub roughness: f32,
+    pub ior: f32,
+}
+
+impl PrincipledBsdf<'_> {
+    /// Weights of the diffuse, specular, clearcoat and transmission lobes.
+    fn weights(&self) -> [f32; 4] {
+        let p = self.parameters;
+        let dielectric = 1. - p.metallic;
+        [
+            dielectric * (1. - p.transmission),
+            1. - dielectric * p.transmission,
+            0.25 * p.clearcoat,
+            dielectric * p.transmission,
+        ]
+    }
+
+    /// The base color with its brightness taken out.
+    fn tint(&self) -> Color {
+        let luminance = self.base_color.luminance();
+        if luminance <= 0. {
+            return WHITE
+        }
+        self.base_color * (1. / luminance)
+    }
+
+    /// Head on reflectance of the dielectric highlight.
+    fn dielectric_f0(&self) -> Color {
+        let p = self.parameters;
+        mix(WHITE, self.tint(), p.specular_tint) * (0.08 * p.specular)
+    }
+
+    fn specular(&self) -> MicrofacetReflection<impl Fn(f32) -> Color> {
+        let f0 = mix(self.dielectric_f0(), self.base_color, self.parameters.metallic);
+        MicrofacetReflection { ggx: Ggx::from_roughness(self.roughness), fresnel: move |cos| fresnel_schlick(cos, f0) }
+    }
+
+    fn clearcoat(&self) -> MicrofacetReflection<impl Fn(f32) -> Color> {
+        let f0 = Color::new(0.04, 0.04, 0.04);
+        MicrofacetReflection {
+            ggx: Ggx::from_roughness(self.parameters.clearcoat_roughness),
+            fresnel: move |cos| fresnel_schlick(cos, f0),
+        }
+    }
+
+    /// Diffuse with retro-reflection at grazing angles on rough surfaces, and
+    /// sheen, times the cosine of `incoming`.
+    fn diffuse(&self, shading: &Shading, incoming: Vec3d) -> Color {
+        let cos_incoming = incoming.dot(&shading.normal);
+        if cos_incoming <= 0. {
+            return BLACK
+        }
+        let cos_outgoing = shading.outgoing.dot(&shading.normal).max(0.);
+        let half = (shading.outgoing + incoming).normalize();
+        let cos_difference = incoming.dot(&half).max(0.);
+        let schlick = |cos: f32| (1. - cos).powi(5);
+        let retro = 0.5 + 2. * self.roughness * cos_difference * cos_difference;
+        let diffuse = (1. + (retro - 1.) * schlick(cos_incoming)) * (1. + (retro - 1.) * schlick(cos_outgoing)) / PI;
+        let p = self.parameters;
+        let sheen = mix(WHITE, self.tint(), p.sheen_tint) * (p.sheen * schlick(cos_difference));
+        // the highlight already reflects its share of the light towards `outgoing`
+        let transmitted = 1. - fresnel_schlick(cos_outgoing, self.dielectric_f0()).luminance();
+        (self.base_color * diffuse + sheen) * (transmitted * cos_incoming)
+    }
+
+    /// Share of the light that passes the clearcoat towards `outgoing`.
+    fn under_clearcoat(&self, shading: &Shading) -> f32 {
+        let cos_outgoing = shading.outgoing.dot(&shading.normal).max(0.);
+        let f0 = Color::new(0.04, 0.04, 0.04);
+        1. - self.weights()[2] * fresnel_schlick(cos_outgoing, f0).luminance()
+    }
+
+    /// Selection probabilities of the lobes, proportional to their weights.
+    fn probabilities(&self) -> [f32; 4] {
+        let weights = self.weights();
+        let total: f32 = weights.iter().sum();
+        weights.map(|weight| weight / total)
+    }
+}
+
+impl Bsdf for PrincipledBsdf<'_> {
+    fn sample(&self, shading: &Shading) -> Option<Sample> {
+        let [diffuse, specular, clearcoat, transmission] = self.probabilities();
+        let choice = rand::get_random_float();
+        if choice < transmission {
+            // a delta lobe, none of the others can pick its direction
+            let glass = SmoothDielectric { ior: self.ior, tint: self.base_color };
+            let sample = glass.sample(shading)?;
+            let weight = sample.weight * (self.weights()[3] / transmission);
+            return Some(Sample { direction: sample.direction, weight })
+        }
+        let direction = if choice < transmission + diffuse {
+            Lambertian { albedo: self.base_color }.sample(shading)?.direction
+        } else if choice < transmission + diffuse + specular {
+            self.specular().sample(shading)?.direction
+        } else if clearcoat > 0. {
+            self.clearcoat().sample(shading)?.direction
+        } else {
+            return None
+        };
+        // one-sample estimate over the lobes that could have picked the direction
+        let pdf = self.pdf(shading, direction);
+        if pdf <= 0. || !pdf.is_finite() {
+            return None
+        }
+        Some(Sample { direction, weight: self.evaluate(shading, direction) * (1. / pdf) })
+    }
+
+    fn evaluate(&self, shading: &Shading, incoming: Vec3d) -> Color {
+        let [diffuse, specular, clearcoat, _] = self.weights();
+        let base = self.diffuse(shading, incoming) * diffuse + self.specular().evaluate(shading, incoming) * specular;
+        base * self.under_clearcoat(shading) + self.clearcoat().evaluate(shading, incoming) * clearcoat
+    }
+
+    fn pdf(&self, shading: &Shading, incoming: Vec3d) -> f32 {
+        let [diffuse, specular, clearcoat, _] = self.probabilities();
+        let lambertian = Lambertian { albedo: self.base_color };
+        diffuse * lambertian.pdf(shading, incoming)
+            + specular * self.specular().pdf(shading, incoming)
+            + clearcoat * self.clearcoat().pdf(shading, incoming)
+    }
+}
+
+fn mix(from: Color, to: Color, t: f32) -> Color {
+    from * (1. - t) + to * t
+}
+
+// w = v - 2 * (v ∙ n) * n
+fn mirror_direction(ray_direction: Vec3d, normal: Vec3d) -> Vec3d {
+    ray_direction - 2. * ray_direction.dot(&normal) * normal
+}
+
+/// Reflected fraction of unpolarized light at a smooth boundary, where
+/// `eta` is the index of refraction of the incident over the transmitted side.
+fn fresnel_dielectric(cos_incident: f32, eta: f32) -> f32 {
+    let sin2_transmitted = eta * eta * (1. - cos_incident * cos_incident);
+    if sin2_transmitted >= 1. {
+        return 1.
+    }
+    let cos_transmitted = (1. - sin2_transmitted).sqrt();
+    let perpendicular = (eta * cos_incident - cos_transmitted) / (eta * cos_incident + cos_transmitted);
+    let parallel = (cos_incident - eta * cos_transmitted) / (cos_incident + eta * cos_transmitted);
+    (perpendicular * perpendicular + parallel * parallel) / 2.
+}
+
+/// Snell's law for a unit direction, the normal faces the incoming ray.
+fn refract_direction(direction: Vec3d, normal: Vec3d, cos_incident: f32, eta: f32) -> Vec3d {
+    let sin2_transmitted = eta * eta * (1. - cos_incident * cos_incident);
+    let cos_transmitted = (1. - sin2_transmitted).max(0.).sqrt();
+    direction * eta + normal * (eta * cos_incident - cos_transmitted)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fresnel_of_glass() {
+        // ((n - 1) / (n + 1))^2 head on
+        assert!((fresnel_dielectric(1., 1. / 1.5) - 0.04).abs() < 1e-6);
+        assert!(fresnel_dielectric(0., 1. / 1.5) == 1.);
+        // past the critical angle of about 41.8° from inside
+        assert!(fresnel_dielectric(f32::cos(0.8), 1.5) == 1.);
+        assert!(fresnel_dielectric(f32::cos(0.7), 1.5) < 1.);
+    }
+
+    #[test]
+    fn refraction_bends_towards_the_normal() {
+        let normal = Vec3d::new(0., 0., 1.);
+        let direction = Vec3d::new(1., 0., -1.).normalize();
+        let cos_incident = -direction.dot(&normal);
+        let refracted = refract_direction(direction, normal, cos_incident, 1. / 1.5);
+
+        assert!((refracted.len() - 1.).abs() < 1e-5);
+        // n1 sin(θ1) = n2 sin(θ2)
+        assert!((refracted.x * 1.5 - direction.x).abs() < 1e-5);
+        assert!(refracted.z < 0.);
+    }
+
+    #[test]
+    fn black_plastic_absorbs_everything() {
+        // without any reflectance the layers can't be weighted against each other
+        let shading = Shading {
+            outgoing: Vec3d::new(0., 0., 1.),
+            normal: Vec3d::new(0., 0., 1.),
+            front_face: true,
+        };
+        let plastic = Plastic {
+            diffuse: Lambertian { albedo: BLACK },
+            specular: MicrofacetReflection { ggx: Ggx::from_roughness(0.5), fresnel: |_| BLACK },
+            specular_weight: 0.,
+        };
+
+        assert!(plastic.sample(&shading).is_none());
+        assert!(plastic.evaluate(&shading, shading.normal).luminance() == 0.);
+        assert!(plastic.pdf(&shading, shading.normal) == 0.);
+    }
+
+    #[test]
+    fn principled_conserves_energy() {
+        // a white furnace: the average weight is the reflected share of the light
+        let shading = Shading {
+            outgoing: Vec3d::new(0.5, 0., 1.).normalize(),
+            normal: Vec3d::new(0., 0., 1.),
+            front_face: true,
+        };
+        let parameters = Principled { clearcoat: 1., sheen: 0.5, ..Principled::default() };
+        // the GGX lobes only scatter once, so the light they shadow is lost,
+        // which makes rough metals darker, while the rough diffuse lobe
+        // retro-reflects slightly more than a Lambertian one
+        for (metallic, roughness, expected) in
+            [(0., 0.5, 1.), (1., 0.2, 0.98), (0.5, 1., 0.67), (0., 0., 0.975), (0., 1., 1.005)]
+        {
+            let bsdf = PrincipledBsdf {
+                parameters: &Principled { metallic, ..parameters },
+                base_color: Color::new(1., 1., 1.),
+                roughness,
+                ior: 1.5,
+            };
+            let count = 50_000;
+            let mut total = 0.;
+            for _ in 0..count {
+                if let Some(sample) = bsdf.sample(&shading) {
+                    assert!(sample.direction.dot(&shading.normal) > 0.);
+                    total += sample.weight.luminance() / count as f32;
+                }
+            }
+            assert!((total - expected).abs() < 0.015, "{metallic} {roughness}: {total}");
+        }
+    }
+}
@@ -0,0 +1,348 @@
+use crate::primitives::Ray;
+use crate::primitives::vec3d::Point3d;
+
+use super::bounding_box::BoundingBox;
+
+const BIN_COUNT: usize = 12;
+const MAX_LEAF_SIZE: usize = 4;
+const TRAVERSAL_COST: f32 = 1.;
+const INTERSECTION_COST: f32 = 1.;
+const STACK_SIZE: usize = 64;
+// traversal pushes at most one node per level on top of the one it pops,
+// so capping the depth keeps the fixed size stacks from overflowing
+const MAX_DEPTH: usize = STACK_SIZE - 1;
+
+/// Bounding volume hierarchy over a list of primitives, built with a binned
+/// surface area heuristic. The tree only knows the primitives by their index
+/// in the slice of bounding boxes it was built from.
+#[derive(Debug)]
+pub struct Bvh {
+    nodes: Vec<BvhNode>,
+    indices: Vec<usize>,
+}
+
+#[derive(Debug)]
+struct BvhNode {
+    bounding_box: BoundingBox,
+    // for a leaf: position of its first primitive in `indices`,
+    // for an inner node: index of the left child, the right one follows it
+    first: usize,
+    count: usize,
+}
+
+#[derive(Clone, Copy)]
+struct Bin {
+    bounding_box: BoundingBox,
+    count: usize,
+}
+
+impl BvhNode {
+    fn is_leaf(&self) -> bool {
+        self.count > 0
+    }
+}
+
+impl Bvh {
+    pub fn build(boxes: &[BoundingBox]) -> Bvh {
+        let centroids: Vec<_> = boxes.iter().map(|b| b.centroid()).collect();
+        let mut bvh = Bvh {
+            nodes: Vec::with_capacity(boxes.len() * 2),
+            indices: (0..boxes.len()).collect(),
+        };
+        bvh.nodes.push(BvhNode {
+            bounding_box: BoundingBox::empty(),
+            first: 0,
+            count: boxes.len(),
+        });
+        if !boxes.is_empty() {
+            bvh.subdivide(0, 0, boxes, &centroids);
+        }
+        bvh
+    }
+
+    pub fn bounding_box(&self) -> BoundingBox {
+        self.nodes[0].bounding_box
+    }
+
+    fn subdivide(&mut self, node_index: usize, depth: usize, boxes: &[BoundingBox], centroids: &[Point3d]) {
+        let first = self.nodes[node_index].first;
+        let count = self.nodes[node_index].count;
+        let items = &mut self.indices[first..first + count];
+
+        let mut bounding_box = BoundingBox::empty();
+        let mut centroid_box = BoundingBox::empty();
+        for &i in items.iter() {
+            bounding_box = bounding_box.union(&boxes[i]);
+            centroid_box.grow(centroids[i]);
+        }
+        self.nodes[node_index].bounding_box = bounding_box;
+
+        if count <= MAX_LEAF_SIZE || depth >= MAX_DEPTH {
+            return
+        }
+
+        let leaf_cost = INTERSECTION_COST * count as f32;
+        let Some((axis, split, split_cost)) = find_best_split(items, boxes, centroids, &centroid_box) else {
+            return
+        };
+        let parent_area = bounding_box.surface_area();
+        let split_cost = TRAVERSAL_COST + INTERSECTION_COST * split_cost / parent_area;
+        if parent_area > 0. && split_cost >= leaf_cost {
+            return
+        }
+
+        // partition the primitives in place around the chosen plane
+        let mut left_count = 0;
+        for i in 0..items.len() {
+            if bin_index(centroids[items[i]][axis], &centroid_box, axis) < split {
+                items.swap(i, left_count);
+                left_count += 1;
+            }
+        }
+        if left_count == 0 || left_count == count {
+            return
+        }
+
+        let left = self.nodes.len();
+        self.nodes.push(BvhNode { bounding_box: BoundingBox::empty(), first, count: left_count });
+        self.nodes.push(BvhNode {
+            bounding_box: BoundingBox::empty(),
+            first: first + left_count,
+            count: count - left_count,
+        });
+        self.nodes[node_index].first = left;
+        self.nodes[node_index].count = 0;
+
+        self.subdivide(left, depth + 1, boxes, centroids);
+        self.subdivide(left + 1, depth + 1, boxes, centroids);
+    }
+
+    /// Walks the tree front to back and returns the index and distance of the
+    /// closest primitive. `intersect` gets a primitive index together with the
+    /// closest distance found so far and returns the distance to that primitive.
+    pub fn closest_hit<F>(&self, ray: &Ray, mut intersect: F) -> Option<(usize, f32)>
+    where
+        F: FnMut(usize, f32) -> Option<f32>,
+    {
+        if self.indices.is_empty() {
+            return None
+        }
+        let mut closest: Option<(usize, f32)> = None;
+        let mut closest_distance = f32::MAX;
+        let mut stack = [(0_usize, 0_f32); STACK_SIZE];
+        let mut stack_len = 0;
+
+        if let Some(distance) = self.nodes[0].bounding_box.intersect(ray, closest_distance) {
+            stack[0] = (0, distance);
+            stack_len = 1;
+        }
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let (node_index, entry_distance) = stack[stack_len];
+            if entry_distance > closest_distance {
+                continue
+            }
+            let node = &self.nodes[node_index];
+            if node.is_leaf() {
+                for &i in &self.indices[node.first..node.first + node.count] {
+                    if let Some(distance) = intersect(i, closest_distance)
+                        && distance < closest_distance
+                    {
+                        closest_distance = distance;
+                        closest = Some((i, distance));
+                    }
+                }
+                continue
+            }
+
+            let left = node.first;
+            let right = node.first + 1;
+            let left_hit = self.nodes[left].bounding_box.intersect(ray, closest_distance);
+            let right_hit = self.nodes[right].bounding_box.intersect(ray, closest_distance);
+            // the nearer child is pushed last so it is visited first
+            match (left_hit, right_hit) {
+                (Some(l), Some(r)) => {
+                    let (near, far) = if l <= r { ((left, l), (right, r)) } else { ((right, r), (left, l)) };
+                    stack[stack_len] = far;
+                    stack[stack_len + 1] = near;
+                    stack_len += 2;
+                }
+                (Some(l), None) => {
+                    stack[stack_len] = (left, l);
+                    stack_len += 1;
+                }
+                (None, Some(r)) => {
+                    stack[stack_len] = (right, r);
+                    stack_len += 1;
+                }
+                (None, None) => {}
+            }
+        }
+        closest
+    }
+
+    /// Stops at the first primitive for which `occludes` returns true.
+    /// Subtrees entirely beyond `t_max` are skipped.
+    pub fn any_hit<F>(&self, ray: &Ray, t_max: f32, mut occludes: F) -> bool
+    where
+        F: FnMut(usize) -> bool,
+    {
+        if self.indices.is_empty() {
+            return false
+        }
+        let mut stack = [0_usize; STACK_SIZE];
+        let mut stack_len = 0;
+
+        if self.nodes[0].bounding_box.intersect(ray, t_max).is_some() {
+            stack[0] = 0;
+            stack_len = 1;
+        }
+
+        while stack_len > 0 {
+            stack_len -= 1;
+            let node = &self.nodes[stack[stack_len]];
+            if node.is_leaf() {
+                for &i in &self.indices[node.first..node.first + node.count] {
+                    if occludes(i) {
+                        return true
+                    }
+                }
+                continue
+            }
+            for child in [node.first, node.first + 1] {
+                if self.nodes[child].bounding_box.intersect(ray, t_max).is_some() {
+                    stack[stack_len] = child;
+                    stack_len += 1;
+                }
+            }
+        }
+        false
+    }
+}
+
+fn bin_index(centroid: f32, centroid_box: &BoundingBox, axis: usize) -> usize {
+    let min = centroid_box.min[axis];
+    let extent = centroid_box.max[axis] - min;
+    let bin = ((centroid - min) / extent * BIN_COUNT as f32) as usize;
+    usize::min(bin, BIN_COUNT - 1)
+}
+
+/// Returns the split axis, the first bin of the right half and the unnormalized
+/// SAH cost (sum of child areas weighted by their primitive counts).
+fn find_best_split(
+    items: &[usize],
+    boxes: &[BoundingBox],
+    centroids: &[Point3d],
+    centroid_box: &BoundingBox,
+) -> Option<(usize, usize, f32)> {
+    let mut best: Option<(usize, usize, f32)> = None;
+    for axis in 0..3 {
+        if centroid_box.max[axis] <= centroid_box.min[axis] {
+            continue
+        }
+        let mut bins = [Bin { bounding_box: BoundingBox::empty(), count: 0 }; BIN_COUNT];
+        for &i in items {
+            let bin = &mut bins[bin_index(centroids[i][axis], centroid_box, axis)];
+            bin.bounding_box = bin.bounding_box.union(&boxes[i]);
+            bin.count += 1;
+        }
+
+        // sweep from the right to get the area and count of every right half
+        let mut right_areas = [0_f32; BIN_COUNT];
+        let mut right_counts = [0_usize; BIN_COUNT];
+        let mut right_box = BoundingBox::empty();
+        let mut right_count = 0;
+        for b in (1..BIN_COUNT).rev() {
+            right_box = right_box.union(&bins[b].bounding_box);
+            right_count += bins[b].count;
+            right_areas[b] = right_box.surface_area();
+            right_counts[b] = right_count;
+        }
+
+        let mut left_box = BoundingBox::empty();
+        let mut left_count = 0;
+        for split in 1..BIN_COUNT {
+            left_box = left_box.union(&bins[split - 1].bounding_box);
+            left_count += bins[split - 1].count;
+            if left_count == 0 || right_counts[split] == 0 {
+                continue
+            }
+            let cost = left_box.surface_area() * left_count as f32
+                + right_areas[split] * right_counts[split] as f32;
+            if best.is_none_or(|(_, _, best_cost)| cost < best_cost) {
+                best = Some((axis, split, cost));
+            }
+        }
+    }
+    best
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Bvh;
+    use crate::primitives::Ray;
+    use crate::surface::bounding_box::BoundingBox;
+    use crate::vec3d::{Point3d, Vec3d};
+
+    fn unit_box(x: f32) -> BoundingBox {
+        BoundingBox {
+            min: Point3d::new(x, 0., 0.),
+            max: Point3d::new(x + 1., 1., 1.),
+        }
+    }
+
+    #[test]
+    fn closest_hit_is_nearest_box() {
+        let boxes: Vec<_> = (0..100).map(|i| unit_box(i as f32 * 2.)).collect();
+        let bvh = Bvh::build(&boxes);
+        let ray = Ray { origin: Point3d::new(-10., 0.5, 0.5), direction: Vec3d::new(1., 0., 0.) };
+
+        let hit = bvh.closest_hit(&ray, |i, t_max| boxes[i].intersect(&ray, t_max));
+
+        let (index, distance) = hit.unwrap();
+        assert!(index == 0);
+        assert!(distance == 10.);
+    }
+
+    #[test]
+    fn closest_hit_visits_every_candidate_from_behind() {
+        let boxes: Vec<_> = (0..100).map(|i| unit_box(i as f32 * 2.)).collect();
+        let bvh = Bvh::build(&boxes);
+        let ray = Ray { origin: Point3d::new(500., 0.5, 0.5), direction: Vec3d::new(-1., 0., 0.) };
+
+        let hit = bvh.closest_hit(&ray, |i, t_max| boxes[i].intersect(&ray, t_max));
+
+        assert!(hit.unwrap().0 == 99);
+    }
+
+    #[test]
+    fn any_hit_respects_t_max() {
+        let boxes: Vec<_> = (0..100).map(|i| unit_box(i as f32 * 2.)).collect();
+        let bvh = Bvh::build(&boxes);
+        let ray = Ray { origin: Point3d::new(-10., 0.5, 0.5), direction: Vec3d::new(1., 0., 0.) };
+        let occludes = |i: usize| boxes[i].intersect(&ray, f32::MAX).is_some();
+
+        assert!(bvh.any_hit(&ray, 11., occludes));
+        assert!(!bvh.any_hit(&ray, 9., occludes));
+    }
+
+    #[test]
+    fn empty_tree_is_never_hit() {
+        let bvh = Bvh::build(&[]);
+        let ray = Ray { origin: Point3d::new(0., 0., 0.), direction: Vec3d::new(1., 0., 0.) };
+
+        assert!(bvh.closest_hit(&ray, |_, _| Some(1.)).is_none());
+        assert!(!bvh.any_hit(&ray, f32::MAX, |_| true));
+        assert!(BoundingBox::empty().intersect(&ray, f32::MAX).is_none());
+    }
+
+    #[test]
+    fn closest_hit_misses() {
+        let boxes: Vec<_> = (0..10).map(|i| unit_box(i as f32 * 2.)).collect();
+        let bvh = Bvh::build(&boxes);
+        let ray = Ray { origin: Point3d::new(-10., 5., 0.5), direction: Vec3d::new(1., 0., 0.) };
+
+        assert!(bvh.closest_hit(&ray, |i, t_max| boxes[i].intersect(&ray, t_max)).is_none());
+    }
+}
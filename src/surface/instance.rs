@@ -0,0 +1,140 @@
+use std::sync::Arc;
+
+use crate::primitives::transform::Transform;
+use crate::primitives::vec3d::Point3d;
+use crate::primitives::Ray;
+
+use super::bounding_box::BoundingBox;
+use super::{HitRecord, Hittable};
+
+/// A placement of a shared object, usually a mesh, in the world. The object
+/// stays in its own space, rays are brought into that space for intersection.
+pub struct Instance {
+    object: Arc<dyn Hittable>,
+    to_world: Transform,
+    to_object: Transform,
+}
+
+impl Instance {
+    pub fn new(object: Arc<dyn Hittable>, to_world: Transform) -> Instance {
+        Instance { object, to_world, to_object: to_world.inverse() }
+    }
+
+    // the direction is not normalized, so distances along the ray
+    // are the same in both spaces
+    fn to_object_space(&self, ray: &Ray) -> Ray {
+        Ray {
+            origin: self.to_object.apply_point(ray.origin),
+            direction: self.to_object.apply_vector(ray.direction),
+        }
+    }
+}
+
+impl Hittable for Instance {
+    fn intersect(&self, ray: &Ray) -> Option<HitRecord<'_>> {
+        let mut hit = self.object.intersect(&self.to_object_space(ray))?;
+        hit.point = ray.origin + ray.direction * hit.t;
+        // the transformed normals still face the ray, as directions and normals
+        // transform so that their dot product is preserved
+        hit.geometric_normal = self.to_world.apply_normal(hit.geometric_normal).normalize();
+        hit.shading_normal = self.to_world.apply_normal(hit.shading_normal).normalize();
+        Some(hit)
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let local = self.object.bounding_box();
+        let mut bounding_box = BoundingBox::empty();
+        for i in 0..8 {
+            let corner = Point3d::new(
+                if i & 1 == 0 { local.min.x } else { local.max.x },
+                if i & 2 == 0 { local.min.y } else { local.max.y },
+                if i & 4 == 0 { local.min.z } else { local.max.z },
+            );
+            bounding_box.grow(self.to_world.apply_point(corner));
+        }
+        bounding_box
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.object.occluded(&self.to_object_space(ray), t_max)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::FRAC_PI_4;
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::primitives::vec3d::{assert_close, Vec3d};
+    use crate::primitives::Color;
+    use crate::surface::material::{Material, MaterialKind};
+    use crate::surface::mesh::Mesh;
+    use crate::surface::sphere::Sphere;
+    use crate::surface::triangle::Triangle;
+
+    fn material() -> Material {
+        Material::new(MaterialKind::Diffuse, Color::new(1., 1., 1.))
+    }
+
+    fn unit_sphere() -> Arc<dyn Hittable> {
+        Arc::new(Sphere::new(Point3d::new(0., 0., 0.), 1., material()))
+    }
+
+    fn ray() -> Ray {
+        Ray { origin: Point3d::new(-10., 0., 0.), direction: Vec3d::new(1., 0., 0.) }
+    }
+
+    /// A sphere of radius 2 around (3, 0, 0) in the world.
+    fn moved_sphere() -> Instance {
+        let to_world = Transform::translate(Vec3d::new(3., 0., 0.)) * Transform::scale(2., 2., 2.);
+        Instance::new(unit_sphere(), to_world)
+    }
+
+    #[test]
+    fn hit_is_in_world_space() {
+        let instance = moved_sphere();
+        let hit = instance.intersect(&ray()).unwrap();
+
+        assert!((hit.t - 11.).abs() < 1e-5);
+        assert_close(hit.point, Point3d::new(1., 0., 0.));
+        assert_close(hit.geometric_normal, Vec3d::new(-1., 0., 0.));
+    }
+
+    #[test]
+    fn normal_under_non_uniform_scale() {
+        // the plane x + y = 0, stretched along x into x / 4 + y = 0
+        let triangle = Triangle::new(
+            Point3d::new(1., -1., -2.),
+            Point3d::new(-1., 1., -2.),
+            Point3d::new(0., 0., 2.),
+            Arc::new(material()),
+        );
+        let instance = Instance::new(Arc::new(Mesh::new(vec![triangle])), Transform::scale(4., 1., 1.));
+        let ray = Ray { origin: Point3d::new(-10., 0.1, 0.), direction: Vec3d::new(1., 0., 0.) };
+
+        let hit = instance.intersect(&ray).unwrap();
+
+        assert_close(hit.point, Point3d::new(-0.4, 0.1, 0.));
+        assert_close(hit.geometric_normal, Vec3d::new(-1., -4., 0.) / 17_f32.sqrt());
+        assert_close(hit.shading_normal, hit.geometric_normal);
+    }
+
+    #[test]
+    fn occluded_in_world_distances() {
+        let instance = moved_sphere();
+
+        assert!(instance.occluded(&ray(), 11.1));
+        assert!(!instance.occluded(&ray(), 10.9));
+    }
+
+    #[test]
+    fn bounding_box_of_rotated_instance() {
+        let instance = Instance::new(unit_sphere(), Transform::rotate(Vec3d::new(0., 0., 1.), FRAC_PI_4));
+        let bounding_box = instance.bounding_box();
+
+        let corner = Vec3d::new(2_f32.sqrt(), 2_f32.sqrt(), 1.);
+        assert_close(bounding_box.min, corner * -1.);
+        assert_close(bounding_box.max, corner);
+    }
+}
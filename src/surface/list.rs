@@ -0,0 +1,132 @@
+use crate::primitives::Ray;
+
+use super::bounding_box::BoundingBox;
+use super::bvh::Bvh;
+use super::{HitRecord, Hittable};
+
+/// A group of objects of any kind with a hierarchy over their bounds.
+pub struct HittableList {
+    objects: Vec<Box<dyn Hittable>>,
+    bvh: Bvh,
+}
+
+impl HittableList {
+    pub fn new(objects: Vec<Box<dyn Hittable>>) -> HittableList {
+        let mut list = HittableList { objects, bvh: Bvh::build(&[]) };
+        list.rebuild();
+        list
+    }
+
+    /// Adds, removes, replaces or moves objects, then rebuilds the hierarchy
+    /// so that it never falls behind the objects.
+    pub fn edit(&mut self, edit: impl FnOnce(&mut Vec<Box<dyn Hittable>>)) {
+        edit(&mut self.objects);
+        self.rebuild();
+    }
+
+    pub fn into_objects(self) -> Vec<Box<dyn Hittable>> {
+        self.objects
+    }
+
+    fn rebuild(&mut self) {
+        let boxes: Vec<BoundingBox> = self.objects.iter().map(|object| object.bounding_box()).collect();
+        self.bvh = Bvh::build(&boxes);
+    }
+}
+
+impl Hittable for HittableList {
+    fn intersect(&self, ray: &Ray) -> Option<HitRecord<'_>> {
+        let mut closest = None;
+        self.bvh.closest_hit(ray, |i, closest_distance| {
+            let hit = self.objects[i].intersect(ray)?;
+            if hit.t < closest_distance {
+                closest = Some(hit);
+            }
+            Some(hit.t)
+        });
+        closest
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        self.bvh.bounding_box()
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.bvh.any_hit(ray, t_max, |i| self.objects[i].occluded(ray, t_max))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::primitives::transform::Transform;
+    use crate::primitives::vec3d::{Point3d, Vec3d};
+    use crate::primitives::Color;
+    use crate::surface::instance::Instance;
+    use crate::surface::material::{Material, MaterialKind};
+    use crate::surface::mesh::Mesh;
+    use crate::surface::sphere::Sphere;
+    use crate::surface::triangle::Triangle;
+
+    fn material() -> Material {
+        Material::new(MaterialKind::Diffuse, Color::new(1., 1., 1.))
+    }
+
+    fn sphere(x: f32) -> Box<dyn Hittable> {
+        Box::new(Sphere::new(Point3d::new(x, 0., 0.), 1., material()))
+    }
+
+    /// A triangle facing -x, placed at `x` by an instance.
+    fn wall(x: f32) -> Box<dyn Hittable> {
+        let triangle = Triangle::new(
+            Point3d::new(0., -2., -2.),
+            Point3d::new(0., 0., 2.),
+            Point3d::new(0., 2., -2.),
+            Arc::new(material()),
+        );
+        let mesh = Arc::new(Mesh::new(vec![triangle]));
+        Box::new(Instance::new(mesh, Transform::translate(Vec3d::new(x, 0., 0.))))
+    }
+
+    fn ray() -> Ray {
+        Ray { origin: Point3d::new(-10., 0., 0.), direction: Vec3d::new(1., 0., 0.) }
+    }
+
+    #[test]
+    fn nearest_of_sphere_and_instance() {
+        let list = HittableList::new(vec![sphere(5.), wall(2.)]);
+        assert!(list.intersect(&ray()).unwrap().t == 12.);
+
+        let list = HittableList::new(vec![sphere(0.), wall(2.)]);
+        assert!(list.intersect(&ray()).unwrap().t == 9.);
+    }
+
+    #[test]
+    fn occluded_respects_t_max() {
+        let list = HittableList::new(vec![sphere(5.), wall(2.)]);
+
+        assert!(list.occluded(&ray(), 13.));
+        assert!(!list.occluded(&ray(), 11.));
+    }
+
+    #[test]
+    fn empty_list() {
+        let list = HittableList::new(Vec::new());
+
+        assert!(list.intersect(&ray()).is_none());
+        assert!(!list.occluded(&ray(), f32::MAX));
+    }
+
+    #[test]
+    fn edits_rebuild_the_hierarchy() {
+        let mut list = HittableList::new(vec![sphere(5.)]);
+        // a moved object is found at its new place, outside the old bounds
+        list.edit(|objects| objects[0] = sphere(-5.));
+        assert!(list.intersect(&ray()).unwrap().t == 4.);
+
+        list.edit(|objects| objects.push(wall(-8.)));
+        assert!(list.intersect(&ray()).unwrap().t == 2.);
+    }
+}
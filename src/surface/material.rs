@@ -1,13 +1,109 @@
+use std::sync::Arc;
+
 use crate::primitives::Color;
+use crate::texture::Texture;
+
+use super::HitRecord;
+use super::bsdf::{
+    Bsdf, Lambertian, MicrofacetReflection, Plastic, Principled, PrincipledBsdf, Sample, Shading, SmoothDielectric,
+};
+use super::microfacet::{fresnel_schlick, Conductor, Ggx};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone, Copy, PartialEq)]
 pub enum MaterialKind {
+    /// Microfacet reflection with `color` as the reflectance head on.
     Glossy,
     Diffuse,
+    /// Diffuse base under a glossy highlight, `color` weights the first
+    /// and `specular` the second.
+    Plastic,
+    /// Microfacet reflection with the Fresnel term of `conductor`, tinted by `color`.
+    Metal,
+    /// The Disney principled model of `principled` with `color` as the base
+    /// color, `roughness` and `ior`.
+    Principled,
+    /// Smooth glass or water that refracts with `ior`, `color` tints the
+    /// transmitted light.
+    Dielectric,
 }
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Material {
     pub material_kind: MaterialKind,
     pub color: Color,
-}
\ No newline at end of file
+    /// Multiplies `color` if present.
+    pub texture: Option<Arc<dyn Texture>>,
+    pub specular: Color,
+    /// Microfacet roughness of reflections in [0, 1], 0 is a perfect mirror.
+    pub roughness: f32,
+    pub conductor: Conductor,
+    pub principled: Principled,
+    pub ior: f32,
+    /// Beer–Lambert absorption coefficient per unit of distance travelled
+    /// inside a dielectric, tinting thick parts more than thin ones.
+    pub absorption: Color,
+    /// Opacity, the rest of the light passes straight through the surface.
+    pub dissolve: f32,
+    pub emission: Color,
+}
+
+impl Material {
+    /// Opaque, non-emissive material without a highlight.
+    pub fn new(material_kind: MaterialKind, color: Color) -> Material {
+        Material {
+            material_kind,
+            color,
+            texture: None,
+            specular: Color::new(0., 0., 0.),
+            roughness: 0.,
+            conductor: Conductor::ALUMINIUM,
+            principled: Principled::default(),
+            ior: 1.5,
+            absorption: Color::new(0., 0., 0.),
+            dissolve: 1.,
+            emission: Color::new(0., 0., 0.),
+        }
+    }
+
+    /// The diffuse color at a hit.
+    pub fn albedo(&self, hit: &HitRecord) -> Color {
+        match &self.texture {
+            Some(texture) => self.color * texture.value(hit.uv, hit.point),
+            None => self.color,
+        }
+    }
+
+    /// Samples the direction the light scattered towards `shading.outgoing`
+    /// comes from. The BSDF is built on the stack for every hit.
+    pub fn sample_bsdf(&self, hit: &HitRecord, shading: &Shading) -> Option<Sample> {
+        let albedo = self.albedo(hit);
+        let ggx = Ggx::from_roughness(self.roughness);
+        match self.material_kind {
+            MaterialKind::Diffuse => Lambertian { albedo }.sample(shading),
+            MaterialKind::Glossy => MicrofacetReflection {
+                ggx,
+                fresnel: |cos| fresnel_schlick(cos, albedo),
+            }
+            .sample(shading),
+            MaterialKind::Metal => MicrofacetReflection {
+                ggx,
+                fresnel: |cos| albedo * self.conductor.fresnel(cos),
+            }
+            .sample(shading),
+            MaterialKind::Plastic => Plastic {
+                diffuse: Lambertian { albedo },
+                specular: MicrofacetReflection { ggx, fresnel: |_| self.specular },
+                specular_weight: self.specular.luminance(),
+            }
+            .sample(shading),
+            MaterialKind::Dielectric => SmoothDielectric { ior: self.ior, tint: albedo }.sample(shading),
+            MaterialKind::Principled => PrincipledBsdf {
+                parameters: &self.principled,
+                base_color: albedo,
+                roughness: self.roughness,
+                ior: self.ior,
+            }
+            .sample(shading),
+        }
+    }
+}
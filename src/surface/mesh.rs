@@ -1,186 +1,254 @@
 use crate::primitives::Ray;
-use crate::surface::Object;
-use crate::vec3d::Point3d;
+use crate::primitives::vec3d::{Point3d, Vec3d};
 use crate::{Color, Material, MaterialKind};
 
+use super::bounding_box::BoundingBox;
+use super::bvh::Bvh;
+use super::mtl;
+use super::obj::{self, ObjData, ObjError};
 use super::triangle::Triangle;
-use super::Surface;
+use super::{HitRecord, Hittable};
 
 use core::f32;
-use std::fs::read_to_string;
-use std::mem::swap;
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
-#[derive(Debug)]
-struct BoundingBox {
-    min: Point3d,
-    max: Point3d,
-}
+/// Faces meeting at a sharper angle than this, in degrees, keep a hard edge.
+pub const DEFAULT_CREASE_ANGLE: f32 = 60.;
 
 pub struct Mesh {
     triangles: Vec<Triangle>,
-    bounding_box: BoundingBox,
-}
-
-impl BoundingBox {
-    pub fn new() -> BoundingBox {
-        BoundingBox {
-            min: Point3d {
-                x: 0f32,
-                y: 0f32,
-                z: 0f32,
-            },
-            max: Point3d {
-                x: 0f32,
-                y: 0f32,
-                z: 0f32,
-            },
-        }
-    }
+    bvh: Bvh,
 }
 
 impl Mesh {
-    pub fn import(path: &str) -> Mesh {
-        let content = read_to_string(path).expect(format!("Could not read file {path}.").as_str());
-        let mut vertices: Vec<Point3d> = Vec::new();
-        let mut triangles: Vec<Triangle> = Vec::new();
-        let mut bounding_box: BoundingBox = BoundingBox::new();
-        for line in content.lines() {
-            let mut split = line.split(' ');
-            match split.next().unwrap() {
-                "v" => {
-                    let x = split.next().unwrap();
-                    let y = split.next().unwrap();
-                    let z = split.next().unwrap();
-                    let vertex =
-                        Point3d::new(x.parse().unwrap(), y.parse().unwrap(), z.parse().unwrap());
-
-                    // updating bounding box
-                    if vertex.x < bounding_box.min.x {
-                        bounding_box.min.x = vertex.x;
-                    } else if vertex.x > bounding_box.max.x {
-                        bounding_box.max.x = vertex.x;
-                    }
-                    if vertex.y < bounding_box.min.y {
-                        bounding_box.min.y = vertex.y;
-                    } else if vertex.y > bounding_box.max.y {
-                        bounding_box.max.y = vertex.y;
-                    }
-                    if vertex.z < bounding_box.min.z {
-                        bounding_box.min.z = vertex.z;
-                    } else if vertex.z > bounding_box.max.z {
-                        bounding_box.max.z = vertex.z;
-                    }
+    pub fn new(triangles: Vec<Triangle>) -> Mesh {
+        let boxes: Vec<BoundingBox> = triangles.iter().map(|t| t.bounding_box()).collect();
+        let bvh = Bvh::build(&boxes);
+        Mesh { triangles, bvh }
+    }
 
-                    vertices.push(vertex);
-                    assert!(split.next() == None)
-                }
-                "f" => {
-                    let a_i = parse_vertex_index(split.next().unwrap());
-                    let b_i = parse_vertex_index(split.next().unwrap());
-                    let c_i = parse_vertex_index(split.next().unwrap());
-                    assert!(split.next() == None, "Only triagonal faces are supported!");
-                    let a = vertices[a_i];
-                    let b = vertices[b_i];
-                    let c = vertices[c_i];
-                    let material = Material {
-                        material_kind: MaterialKind::Diffuse,
-                        color: Color::new(0.8, 0.8, 0.8),
-                    };
-                    let triangle = Triangle::new(a, b, c, material);
-                    triangles.push(triangle);
-                }
-                _ => {} // ignore other objects
+    /// Reads the polygons of an OBJ file together with the materials of its
+    /// MTL libraries, returning an error with the offending line if a file
+    /// is malformed. Faces without a known material are grey and diffuse.
+    ///
+    /// Triangles are smooth shaded with the normals of the file. Where it has
+    /// none, only zero length ones, or if a `crease_angle` in degrees is given,
+    /// they are computed from the faces around each vertex.
+    pub fn import(path: &Path, crease_angle: Option<f32>) -> Result<Mesh, ObjError> {
+        let data = obj::load(path)?;
+        let directory = path.parent().unwrap_or(Path::new(""));
+        let mut library = HashMap::new();
+        for file in &data.material_libraries {
+            match mtl::load(&directory.join(file)) {
+                Ok(materials) => library.extend(materials),
+                // the geometry is still usable without its materials
+                Err(ObjError { line: None, .. }) => eprintln!("Could not read {file}, using default materials"),
+                Err(e) => return Err(e),
             }
         }
-        println!("Bounding box coordinates {:?}", bounding_box);
-        println!("Number of triangles {:?}", triangles.len());
-        Mesh {
-            triangles,
-            bounding_box,
-        }
+        let default_material = Arc::new(Material::new(MaterialKind::Diffuse, Color::new(0.8, 0.8, 0.8)));
+        let materials: Vec<Arc<Material>> = data
+            .material_names
+            .iter()
+            .map(|name| match library.remove(name) {
+                Some(material) => Arc::new(material),
+                None => {
+                    eprintln!("Unknown material {name} in {}", path.display());
+                    default_material.clone()
+                }
+            })
+            .collect();
+        // a zero length normal can't be normalized, it counts as missing
+        let file_normals: Vec<[Option<Vec3d>; 3]> = data
+            .faces
+            .iter()
+            .map(|face| {
+                face.vertices.map(|vertex| {
+                    let normal = data.normals[vertex.normal?];
+                    if normal.len_squared() > 0. { Some(normal.normalize()) } else { None }
+                })
+            })
+            .collect();
+        let has_all_normals = file_normals.iter().flatten().all(|normal| normal.is_some());
+        let computed = if has_all_normals && crease_angle.is_none() {
+            Vec::new()
+        } else {
+            vertex_normals(&data, crease_angle.unwrap_or(DEFAULT_CREASE_ANGLE))
+        };
+        let triangles = data
+            .faces
+            .iter()
+            .enumerate()
+            .map(|(i, face)| {
+                let [a, b, c] = face.vertices.map(|vertex| data.positions[vertex.position]);
+                let material = face.material.map_or(&default_material, |i| &materials[i]).clone();
+                let normals = match file_normals[i] {
+                    [Some(a), Some(b), Some(c)] if crease_angle.is_none() => [a, b, c],
+                    _ => computed[i],
+                };
+                let triangle = Triangle::new(a, b, c, material).with_normals(normals);
+                match face.vertices.map(|vertex| vertex.texture.map(|i| data.texture_coordinates[i])) {
+                    [Some(a), Some(b), Some(c)] => triangle.with_uvs([a, b, c]),
+                    _ => triangle,
+                }
+            })
+            .collect();
+        let mesh = Mesh::new(triangles);
+        println!("Bounding box coordinates {:?}", mesh.bvh.bounding_box());
+        println!("Number of triangles {:?}", mesh.triangles.len());
+        Ok(mesh)
     }
 }
 
-impl Object for Mesh {
-    fn intersect(&self, ray: &Ray) -> (f32, Option<&Triangle>) {
-        let mut closest_distance = f32::MAX;
-        let mut closest_triangle = None;
-
-        // check if ray hits bounding box
-        if hits_boudning_box(ray, &self.bounding_box) {
-            for triangle in &self.triangles {
-                match triangle.intersect(ray) {
-                    Some(distance) => {
-                        if distance < closest_distance {
-                            closest_distance = distance;
-                            closest_triangle = Some(triangle);
-                        }
-                    }
-                    None => continue,
-                }
+impl Hittable for Mesh {
+    fn intersect(&self, ray: &Ray) -> Option<HitRecord<'_>> {
+        let mut closest_uv = (0., 0.);
+        let (i, distance) = self.bvh.closest_hit(ray, |i, closest_distance| {
+            let (distance, u, v) = self.triangles[i].intersect_barycentric(ray)?;
+            if distance < closest_distance {
+                closest_uv = (u, v);
             }
-            //println!("hit")
-        } else {
-            //println!("miss")
-        }
-        return (closest_distance, closest_triangle);
+            Some(distance)
+        })?;
+        let (u, v) = closest_uv;
+        Some(self.triangles[i].hit_record(ray, distance, u, v, i))
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        self.bvh.bounding_box()
     }
-}
 
-fn parse_vertex_index(str: &str) -> usize {
-    str.split('/').next().unwrap().parse::<usize>().unwrap() - 1
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.bvh.any_hit(ray, t_max, |i| self.triangles[i].occluded(ray, t_max))
+    }
 }
 
-fn hits_boudning_box(ray: &Ray, bounding_box: &BoundingBox) -> bool {
-    let mut min_t = f32::MIN;
-    let mut max_t = f32::MAX;
-    let res = intersect_one_dimention(
-        &mut min_t,
-        &mut max_t,
-        ray.direction.x,
-        bounding_box.min.x,
-        bounding_box.max.x,
-        ray.origin.x,
-    ) && intersect_one_dimention(
-        &mut min_t,
-        &mut max_t,
-        ray.direction.y,
-        bounding_box.min.y,
-        bounding_box.max.y,
-        ray.origin.y,
-    ) && intersect_one_dimention(
-        &mut min_t,
-        &mut max_t,
-        ray.direction.z,
-        bounding_box.min.z,
-        bounding_box.max.z,
-        ray.origin.z,
-    ) && max_t > 0.;
-    //if !res {panic!()}
-    return res;
+/// Corner normals of every face, averaged over the faces sharing the vertex
+/// and weighted by their angle at it. Faces whose normals differ by more than
+/// `crease_angle` degrees don't contribute to each other.
+fn vertex_normals(data: &ObjData, crease_angle: f32) -> Vec<[Vec3d; 3]> {
+    let corners: Vec<[Point3d; 3]> = data
+        .faces
+        .iter()
+        .map(|face| face.vertices.map(|vertex| data.positions[vertex.position]))
+        .collect();
+    let face_normals: Vec<Vec3d> = corners
+        .iter()
+        .map(|[a, b, c]| {
+            let normal = (*b - *a).cross(&(*c - *a));
+            if normal.len_squared() > 0. { normal.normalize() } else { normal }
+        })
+        .collect();
+    let angles: Vec<[f32; 3]> = corners
+        .iter()
+        .map(|corners| {
+            [0, 1, 2].map(|i| {
+                let first = corners[(i + 1) % 3] - corners[i];
+                let second = corners[(i + 2) % 3] - corners[i];
+                let cos = first.dot(&second) / (first.len() * second.len());
+                if cos.is_nan() { 0. } else { cos.clamp(-1., 1.).acos() }
+            })
+        })
+        .collect();
+
+    let mut adjacent: Vec<Vec<(usize, usize)>> = vec![Vec::new(); data.positions.len()];
+    for (f, face) in data.faces.iter().enumerate() {
+        for (corner, vertex) in face.vertices.iter().enumerate() {
+            adjacent[vertex.position].push((f, corner));
+        }
+    }
+
+    let min_cos = crease_angle.to_radians().cos();
+    data.faces
+        .iter()
+        .enumerate()
+        .map(|(f, face)| {
+            [0, 1, 2].map(|corner| {
+                let mut sum = Vec3d::new(0., 0., 0.);
+                for &(other, other_corner) in &adjacent[face.vertices[corner].position] {
+                    if face_normals[f].dot(&face_normals[other]) >= min_cos {
+                        sum = sum + face_normals[other] * angles[other][other_corner];
+                    }
+                }
+                if sum.len_squared() > 0. { sum.normalize() } else { face_normals[f] }
+            })
+        })
+        .collect()
 }
 
-fn intersect_one_dimention(
-    min_t: &mut f32,
-    max_t: &mut f32,
-    direction_i: f32,
-    min_i: f32,
-    max_i: f32,
-    origin_i: f32,
-) -> bool {
-    if direction_i != 0. {
-        let min_i_aligned = min_i - origin_i;
-        let max_i_aligned = max_i - origin_i;
-        let direction_i_inv = 1. / direction_i;
-        let mut new_min_t = min_i_aligned * direction_i_inv;
-        let mut new_max_t = max_i_aligned * direction_i_inv;
-        if new_min_t > new_max_t {
-            swap(&mut new_min_t, &mut new_max_t)
-        };
-        *min_t = f32::max(*min_t, new_min_t);
-        *max_t = f32::min(*max_t, new_max_t);
-        return max_t > min_t;
-    } else {
-        return origin_i <= max_i && origin_i >= min_i;
+#[cfg(test)]
+mod tests {
+    use std::f32::consts::PI;
+
+    use super::*;
+    use crate::primitives::vec3d::assert_close;
+
+    fn normals(content: &str, crease_angle: f32) -> Vec<[Vec3d; 3]> {
+        vertex_normals(&obj::parse(content).unwrap(), crease_angle)
+    }
+
+    // two faces folded by 90 degrees along the edge between vertices 1 and 2
+    const FOLD: &str = "v 0 0 0\nv 0 1 0\nv 1 0 0\nv 0 0 1\nf 1 2 3\nf 2 1 4\n";
+
+    #[test]
+    fn mesh_without_faces_is_never_hit() {
+        let path = std::env::temp_dir().join(format!("raytracer-no-faces-{}.obj", std::process::id()));
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\n").unwrap();
+        let mesh = Mesh::import(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        let mesh = mesh.unwrap();
+        let ray = Ray { origin: Point3d::new(0.2, 0.2, 1.), direction: Vec3d::new(0., 0., -1.) };
+
+        assert!(mesh.intersect(&ray).is_none());
+        assert!(!mesh.occluded(&ray, f32::MAX));
+        assert!(mesh.bounding_box().is_empty());
+    }
+
+    #[test]
+    fn zero_length_normals_are_computed() {
+        let path = std::env::temp_dir().join(format!("raytracer-zero-normal-{}.obj", std::process::id()));
+        std::fs::write(&path, "v 0 0 0\nv 1 0 0\nv 0 1 0\nvn 0 0 0\nf 1//1 2//1 3//1\n").unwrap();
+        let mesh = Mesh::import(&path, None);
+        std::fs::remove_file(&path).unwrap();
+        let mesh = mesh.unwrap();
+        let ray = Ray { origin: Point3d::new(0.2, 0.2, 1.), direction: Vec3d::new(0., 0., -1.) };
+
+        let hit = mesh.intersect(&ray).unwrap();
+        assert_close(hit.shading_normal, Vec3d::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn smooth_below_crease_angle() {
+        let normals = normals(FOLD, 100.);
+
+        let expected = Vec3d::new(-1., 0., -1.).normalize();
+        assert_close(normals[0][0], expected);
+        assert_close(normals[1][0], expected);
+        // the vertex only one face touches keeps its normal
+        assert_close(normals[0][2], Vec3d::new(0., 0., -1.));
+    }
+
+    #[test]
+    fn hard_edge_above_crease_angle() {
+        let normals = normals(FOLD, 80.);
+
+        assert_close(normals[0][0], Vec3d::new(0., 0., -1.));
+        assert_close(normals[1][1], Vec3d::new(-1., 0., 0.));
+    }
+
+    #[test]
+    fn weighted_by_angle() {
+        // two coplanar faces with 90 and 45 degrees at the first vertex
+        // and a perpendicular one with 90 degrees
+        let content = "v 0 0 0\nv 1 0 0\nv 0 1 0\nv -1 1 0\nv 0 0 1\nf 1 2 3\nf 1 3 4\nf 1 5 2\n";
+
+        let flat = normals(content, 89.);
+        assert_close(flat[0][0], Vec3d::new(0., 0., 1.));
+
+        let smooth = normals(content, 91.);
+        let expected = (Vec3d::new(0., 0., 1.) * (PI / 2. + PI / 4.) + Vec3d::new(0., 1., 0.) * (PI / 2.)).normalize();
+        assert_close(smooth[0][0], expected);
     }
 }
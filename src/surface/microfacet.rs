@@ -0,0 +1,234 @@
+//! GGX (Trowbridge-Reitz) microfacet reflection with Smith masking and
+//! sampling of the visible normals, after Heitz, "Sampling the GGX
+//! Distribution of Visible Normals", JCGT 2018.
+
+use std::f32::consts::{PI, TAU};
+
+use crate::primitives::rand;
+use crate::primitives::vec3d::Vec3d;
+use crate::primitives::Color;
+
+/// Complex index of refraction `eta + i k` of a metal for red, green and blue.
+#[derive(Debug, Clone, Copy)]
+pub struct Conductor {
+    pub eta: Color,
+    pub k: Color,
+}
+
+impl Conductor {
+    pub const ALUMINIUM: Conductor = Conductor::new([1.657, 0.880, 0.521], [9.224, 6.270, 4.837]);
+    pub const COPPER: Conductor = Conductor::new([0.200, 0.924, 1.102], [3.912, 2.452, 2.142]);
+    pub const GOLD: Conductor = Conductor::new([0.143, 0.374, 1.442], [3.983, 2.385, 1.603]);
+    pub const SILVER: Conductor = Conductor::new([0.155, 0.117, 0.138], [4.828, 3.122, 2.147]);
+
+    const fn new(eta: [f32; 3], k: [f32; 3]) -> Conductor {
+        Conductor {
+            eta: Color { red: eta[0], green: eta[1], blue: eta[2] },
+            k: Color { red: k[0], green: k[1], blue: k[2] },
+        }
+    }
+
+    /// Exact Fresnel reflectance of the metal in air.
+    pub fn fresnel(&self, cos_incident: f32) -> Color {
+        let channel = |eta: f32, k: f32| fresnel_conductor(cos_incident, eta, k);
+        Color::new(
+            channel(self.eta.red, self.k.red),
+            channel(self.eta.green, self.k.green),
+            channel(self.eta.blue, self.k.blue),
+        )
+    }
+}
+
+fn fresnel_conductor(cos_incident: f32, eta: f32, k: f32) -> f32 {
+    let cos2 = cos_incident * cos_incident;
+    let sin2 = 1. - cos2;
+    let t0 = eta * eta - k * k - sin2;
+    let a2_plus_b2 = (t0 * t0 + 4. * eta * eta * k * k).sqrt();
+    let a = (0.5 * (a2_plus_b2 + t0)).max(0.).sqrt();
+    let t1 = a2_plus_b2 + cos2;
+    let t2 = 2. * cos_incident * a;
+    let perpendicular = (t1 - t2) / (t1 + t2);
+    let t3 = cos2 * a2_plus_b2 + sin2 * sin2;
+    let t4 = t2 * sin2;
+    let parallel = perpendicular * (t3 - t4) / (t3 + t4);
+    (perpendicular + parallel) / 2.
+}
+
+/// Schlick's approximation with the reflectance `f0` head on.
+pub fn fresnel_schlick(cos_incident: f32, f0: Color) -> Color {
+    let weight = (1. - cos_incident).clamp(0., 1.).powi(5);
+    f0 * (1. - weight) + Color::new(weight, weight, weight)
+}
+
+/// A sampled reflection.
+pub struct Reflection {
+    pub direction: Vec3d,
+    /// Cosine between the incoming direction and the microfacet normal, for the Fresnel term.
+    pub cos_half: f32,
+    /// Ratio of the masking-shadowing term to the masking of the incoming
+    /// direction, the weight of the sample besides Fresnel.
+    pub shadowing: f32,
+}
+
+/// Narrowest distribution that still has a finite density in single precision.
+const MIN_ALPHA: f32 = 1e-3;
+
+#[derive(Debug, Clone, Copy)]
+pub struct Ggx {
+    alpha: f32,
+}
+
+impl Ggx {
+    /// `roughness` in [0, 1] is squared into the width of the distribution,
+    /// which spreads highlights evenly over its range. Down at `MIN_ALPHA`
+    /// reflections are sampled as of a perfect mirror.
+    pub fn from_roughness(roughness: f32) -> Ggx {
+        let roughness = roughness.clamp(0., 1.);
+        Ggx { alpha: f32::max(roughness * roughness, MIN_ALPHA) }
+    }
+
+    /// Density of microfacet normals at the cosine to the surface normal.
+    fn distribution(&self, cos_half: f32) -> f32 {
+        let alpha2 = self.alpha * self.alpha;
+        let denominator = cos_half * cos_half * (alpha2 - 1.) + 1.;
+        alpha2 / (PI * denominator * denominator)
+    }
+
+    /// Smith's auxiliary function of a direction at the cosine to the normal.
+    fn lambda(&self, cos: f32) -> f32 {
+        let cos2 = cos * cos;
+        let tan2 = (1. - cos2).max(0.) / cos2;
+        ((1. + self.alpha * self.alpha * tan2).sqrt() - 1.) / 2.
+    }
+
+    /// A microfacet normal in the local frame, distributed as the normals
+    /// that face `outgoing` are.
+    fn sample_visible_normal(&self, outgoing: Vec3d) -> Vec3d {
+        // stretch the view so that the distribution becomes a hemisphere
+        let view = Vec3d::new(self.alpha * outgoing.x, self.alpha * outgoing.y, outgoing.z).normalize();
+        let length2 = view.x * view.x + view.y * view.y;
+        let t1 = if length2 > 0. {
+            Vec3d::new(-view.y, view.x, 0.) / length2.sqrt()
+        } else {
+            Vec3d::new(1., 0., 0.)
+        };
+        let t2 = view.cross(&t1);
+        // a point on the projected hemisphere
+        let r = rand::get_random_float().sqrt();
+        let (sin_phi, cos_phi) = (rand::get_random_float() * TAU).sin_cos();
+        let p1 = r * cos_phi;
+        let s = 0.5 * (1. + view.z);
+        let p2 = (1. - s) * (1. - p1 * p1).max(0.).sqrt() + s * r * sin_phi;
+        let normal = t1 * p1 + t2 * p2 + view * (1. - p1 * p1 - p2 * p2).max(0.).sqrt();
+        // and back to the ellipsoid
+        Vec3d::new(self.alpha * normal.x, self.alpha * normal.y, normal.z.max(1e-6)).normalize()
+    }
+
+    /// Reflects the unit direction towards the viewer, `outgoing`, off a
+    /// sampled microfacet of the surface with `normal`. `None` if the
+    /// reflection goes under the surface.
+    pub fn sample_reflection(&self, outgoing: Vec3d, normal: Vec3d) -> Option<Reflection> {
+        let (tangent, bitangent) = normal.orthonormal_basis();
+        let to_local = |v: Vec3d| Vec3d::new(v.dot(&tangent), v.dot(&bitangent), v.dot(&normal));
+        // interpolated normals may face slightly away from the viewer
+        let local = to_local(outgoing);
+        let outgoing = Vec3d::new(local.x, local.y, local.z.max(1e-4)).normalize();
+        let half = if self.alpha <= MIN_ALPHA { Vec3d::new(0., 0., 1.) } else { self.sample_visible_normal(outgoing) };
+        let cos_half = outgoing.dot(&half);
+        let incoming = half * (2. * cos_half) - outgoing;
+        if incoming.z <= 0. {
+            return None
+        }
+        let lambda_outgoing = self.lambda(outgoing.z);
+        Some(Reflection {
+            direction: tangent * incoming.x + bitangent * incoming.y + normal * incoming.z,
+            cos_half,
+            shadowing: (1. + lambda_outgoing) / (1. + lambda_outgoing + self.lambda(incoming.z)),
+        })
+    }
+
+    /// The reflection from `incoming` towards `outgoing`, unit directions
+    /// away from the surface: the cosine to the half vector for the Fresnel
+    /// term, the BRDF times the cosine of `incoming` without Fresnel, and the
+    /// density with which `sample_reflection` picks `incoming`.
+    pub fn evaluate(&self, outgoing: Vec3d, incoming: Vec3d, normal: Vec3d) -> Option<(f32, f32, f32)> {
+        let cos_outgoing = outgoing.dot(&normal).max(1e-4);
+        let cos_incoming = incoming.dot(&normal);
+        if cos_incoming <= 0. {
+            return None
+        }
+        let half = (outgoing + incoming).normalize();
+        let distribution = self.distribution(half.dot(&normal).max(0.));
+        let lambda_outgoing = self.lambda(cos_outgoing);
+        let shadowing = 1. / (1. + lambda_outgoing + self.lambda(cos_incoming));
+        let masking = 1. / (1. + lambda_outgoing);
+        Some((
+            outgoing.dot(&half).max(0.),
+            distribution * shadowing / (4. * cos_outgoing),
+            distribution * masking / (4. * cos_outgoing),
+        ))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn conductor_fresnel() {
+        // without extinction the head on reflectance is ((n - 1) / (n + 1))^2
+        assert!((fresnel_conductor(1., 1.5, 0.) - 0.04).abs() < 1e-5);
+        assert!((fresnel_conductor(0., 1.5, 3.) - 1.).abs() < 1e-5);
+        let gold = Conductor::GOLD.fresnel(1.);
+        assert!(gold.red > gold.green && gold.green > gold.blue);
+    }
+
+    #[test]
+    fn smooth_surfaces_are_mirrors() {
+        let normal = Vec3d::new(0., 0., 1.);
+        let outgoing = Vec3d::new(1., 0., 1.).normalize();
+        let reflection = Ggx::from_roughness(0.).sample_reflection(outgoing, normal).unwrap();
+
+        assert!((reflection.direction - Vec3d::new(-1., 0., 1.).normalize()).len() < 1e-4);
+        assert!((reflection.shadowing - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn rough_reflections_stay_above_the_surface() {
+        let normal = Vec3d::new(0., 1., 0.);
+        let outgoing = Vec3d::new(0.3, 0.8, 0.1).normalize();
+        let ggx = Ggx::from_roughness(0.8);
+        for _ in 0..1000 {
+            if let Some(reflection) = ggx.sample_reflection(outgoing, normal) {
+                assert!(reflection.direction.dot(&normal) > 0.);
+                assert!((0. ..=1.).contains(&reflection.shadowing));
+                assert!(reflection.cos_half > 0.);
+            }
+        }
+    }
+
+    #[test]
+    fn density_matches_sampling() {
+        // the density integrates to the share of sampled reflections that
+        // stay above the surface
+        let normal = Vec3d::new(0., 0., 1.);
+        let outgoing = Vec3d::new(0.2, 0., 1.).normalize();
+        let ggx = Ggx::from_roughness(0.5);
+        let count = 200_000;
+        let sampled = (0..count).filter(|_| ggx.sample_reflection(outgoing, normal).is_some()).count();
+
+        let mut rng = fastrand::Rng::with_seed(5);
+        let mut integral = 0.;
+        for _ in 0..count {
+            // uniform over the hemisphere, a density of 1 / 2π
+            let z = rng.f32();
+            let phi = rng.f32() * TAU;
+            let r = (1. - z * z).sqrt();
+            let incoming = Vec3d::new(r * phi.cos(), r * phi.sin(), z);
+            if let Some((_, _, pdf)) = ggx.evaluate(outgoing, incoming, normal) {
+                integral += pdf as f64 * std::f64::consts::TAU / count as f64;
+            }
+        }
+        assert!((integral - sampled as f64 / count as f64).abs() < 0.02);
+    }
+}
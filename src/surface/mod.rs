@@ -1,19 +1,63 @@
 use crate::primitives::Ray;
 use crate::primitives::vec3d::{Vec3d, Point3d};
-use crate::surface::triangle::Triangle;
+use crate::surface::bounding_box::BoundingBox;
 use crate::Material;
 
 pub mod material;
 pub mod sphere;
 pub mod triangle;
 pub mod mesh;
+pub mod obj;
+pub mod mtl;
+pub mod microfacet;
+pub mod bsdf;
+pub mod instance;
+pub mod list;
+pub mod bounding_box;
+pub mod bvh;
 
-pub trait Surface {
-    fn get_normal(&self, point: Point3d) -> Vec3d;
-    fn intersect(&self, ray: &Ray) -> Option<f32>;
-    fn get_material(&self) -> Material;
+/// Everything known about the point where a ray hits a surface.
+#[derive(Debug, Clone, Copy)]
+pub struct HitRecord<'a> {
+    /// Distance along the ray, in units of its direction's length.
+    pub t: f32,
+    pub point: Point3d,
+    /// Unit normal of the surface itself. Like the shading normal it faces
+    /// the side the ray came from.
+    pub geometric_normal: Vec3d,
+    /// Unit normal for lighting, e.g. interpolated over a triangle.
+    pub shading_normal: Vec3d,
+    pub uv: (f32, f32),
+    /// Weights of a triangle's second and third corner, zero for other shapes.
+    pub barycentrics: (f32, f32),
+    /// Whether the ray hit the outside of the surface.
+    pub front_face: bool,
+    pub material: &'a Material,
+    /// Index of the hit primitive within its object, e.g. the triangle of a mesh.
+    pub primitive_id: usize,
 }
 
-pub trait Object {
-    fn intersect(&self, ray: &Ray) -> (f32, Option<&Triangle>);
-}
\ No newline at end of file
+impl HitRecord<'_> {
+    /// Turns outward normals towards the ray and records which side was hit.
+    pub fn orient(mut self, ray: &Ray) -> Self {
+        self.front_face = ray.direction.dot(&self.geometric_normal) < 0.;
+        if !self.front_face {
+            self.geometric_normal = self.geometric_normal * -1.;
+            self.shading_normal = self.shading_normal * -1.;
+        }
+        self
+    }
+}
+
+/// Anything a ray can hit, from a single primitive to a whole group of objects.
+pub trait Hittable: Send + Sync {
+    /// The closest hit along the ray.
+    fn intersect(&self, ray: &Ray) -> Option<HitRecord<'_>>;
+    fn bounding_box(&self) -> BoundingBox;
+
+    /// Whether anything blocks the ray closer than `t_max`.
+    /// Unlike `intersect` it may stop at the first hit it finds.
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        self.intersect(ray).is_some_and(|hit| hit.t < t_max)
+    }
+}
@@ -0,0 +1,329 @@
+//! Wavefront MTL material libraries. The ambient color `Ka` has no meaning
+//! for a path tracer and is skipped, like every texture map but `map_Kd`
+//! and other statements. The physically based extension written by Blender,
+//! `Pr`, `Pm`, `Ps`, `Pc` and `Pcr`, makes a material principled.
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
+
+use crate::primitives::Color;
+use crate::texture::Texture;
+use crate::texture::image::{ImageTexture, Wrap};
+
+use super::bsdf::Principled;
+use super::material::{Material, MaterialKind};
+use super::microfacet::Conductor;
+use super::obj::{self, ObjError, Result};
+
+/// Material fields as they are written in the file.
+struct MtlMaterial {
+    diffuse: Color,
+    specular: Color,
+    emission: Color,
+    transmission: Color,
+    shininess: Option<f32>,
+    roughness: Option<f32>,
+    principled: Option<Principled>,
+    ior: f32,
+    dissolve: f32,
+    illum: u32,
+    diffuse_map: Option<Arc<dyn Texture>>,
+}
+
+impl Default for MtlMaterial {
+    fn default() -> Self {
+        MtlMaterial {
+            diffuse: Color::new(0.8, 0.8, 0.8),
+            specular: Color::new(0., 0., 0.),
+            emission: Color::new(0., 0., 0.),
+            transmission: Color::new(1., 1., 1.),
+            shininess: None,
+            roughness: None,
+            principled: None,
+            ior: 1.5,
+            dissolve: 1.,
+            illum: 1,
+            diffuse_map: None,
+        }
+    }
+}
+
+impl MtlMaterial {
+    fn to_material(&self) -> Material {
+        let black = |color: Color| color.luminance() <= 0.;
+        let material_kind = match self.illum {
+            _ if self.principled.is_some() => MaterialKind::Principled,
+            // color and ambient only, or with a diffuse term
+            0 | 1 => MaterialKind::Diffuse,
+            // ray traced reflections without a diffuse base
+            3 | 5 | 8 => MaterialKind::Glossy,
+            // glass and refraction
+            4 | 6 | 7 | 9 => MaterialKind::Dielectric,
+            // highlights, possibly with refraction and transparency
+            _ if black(self.specular) => MaterialKind::Diffuse,
+            _ => MaterialKind::Plastic,
+        };
+        let color = match material_kind {
+            MaterialKind::Glossy if !black(self.specular) => self.specular,
+            MaterialKind::Dielectric => self.transmission,
+            _ => self.diffuse,
+        };
+        let mut principled = self.principled.unwrap_or_default();
+        if !black(self.specular) {
+            principled.specular = self.specular.luminance();
+        }
+        if [4, 6, 7, 9].contains(&self.illum) {
+            principled.transmission = 1.;
+        }
+        let roughness = self.roughness.or(self.shininess.map(roughness_from_exponent));
+        Material {
+            material_kind,
+            color,
+            texture: self.diffuse_map.clone(),
+            specular: self.specular,
+            roughness: roughness.unwrap_or(0.),
+            conductor: Conductor::ALUMINIUM,
+            principled,
+            ior: self.ior,
+            absorption: Color::new(0., 0., 0.),
+            dissolve: self.dissolve,
+            emission: self.emission,
+        }
+    }
+}
+
+pub fn load(path: &Path) -> std::result::Result<HashMap<String, Material>, ObjError> {
+    let directory = path.parent().unwrap_or(Path::new(""));
+    obj::read_file(path, |content| parse(content, directory))
+}
+
+/// Parses the content of an MTL file, texture maps are relative to `directory`.
+pub fn parse(content: &str, directory: &Path) -> Result<HashMap<String, Material>> {
+    let mut materials: Vec<(String, MtlMaterial)> = Vec::new();
+    for (line, statement) in obj::statements(content) {
+        let mut words = statement.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue
+        };
+        let values: Vec<&str> = words.collect();
+        if keyword == "newmtl" {
+            if values.is_empty() {
+                return Err((line, "newmtl needs a material name".to_string()))
+            }
+            materials.push((values.join(" "), MtlMaterial::default()));
+            continue
+        }
+        let known = ["Kd", "Ks", "Ke", "Ns", "Ni", "d", "Tr", "Tf", "illum", "map_Kd", "Pr", "Pm", "Ps", "Pc", "Pcr"];
+        if !known.contains(&keyword) {
+            continue
+        }
+        let Some((_, material)) = materials.last_mut() else {
+            return Err((line, format!("{keyword} comes before any newmtl")))
+        };
+        match keyword {
+            "Kd" => material.diffuse = parse_color(line, &values)?,
+            "Ks" => material.specular = parse_color(line, &values)?,
+            "Ke" => material.emission = parse_color(line, &values)?,
+            "Tf" => material.transmission = parse_color(line, &values)?,
+            "Ns" => material.shininess = Some(parse_single(line, &values)?),
+            "Ni" => material.ior = parse_single(line, &values)?,
+            "d" => material.dissolve = parse_single(line, &values)?,
+            "Tr" => material.dissolve = 1. - parse_single(line, &values)?,
+            "Pr" | "Pm" | "Ps" | "Pc" | "Pcr" => {
+                let value = parse_single(line, &values)?;
+                if !(0. ..=1.).contains(&value) {
+                    return Err((line, format!("{keyword} has to be between 0 and 1")))
+                }
+                let principled = material.principled.get_or_insert_default();
+                match keyword {
+                    "Pr" => material.roughness = Some(value),
+                    "Pm" => principled.metallic = value,
+                    "Ps" => principled.sheen = value,
+                    "Pc" => principled.clearcoat = value,
+                    _ => principled.clearcoat_roughness = value,
+                }
+            }
+            "map_Kd" => {
+                let (file, wrap) = parse_map(line, &values)?;
+                // a missing image leaves the plain color, like a missing library
+                match ImageTexture::load(&directory.join(&file)) {
+                    Ok(mut texture) => {
+                        texture.wrap = wrap;
+                        material.diffuse_map = Some(Arc::new(texture));
+                    }
+                    Err(e) => eprintln!("{e}, using the plain color"),
+                }
+            }
+            _ => {
+                let illum = parse_single(line, &values)?;
+                if illum < 0. || illum.fract() != 0. {
+                    return Err((line, format!("Invalid illumination model {illum}")))
+                }
+                material.illum = illum as u32;
+            }
+        }
+    }
+    Ok(materials
+        .into_iter()
+        .map(|(name, material)| (name, material.to_material()))
+        .collect())
+}
+
+/// The roughness of a specular exponent in [0, 1000], as Blender converts them.
+fn roughness_from_exponent(exponent: f32) -> f32 {
+    (1. - exponent.max(0.).sqrt() / 30.).clamp(0., 1.)
+}
+
+fn parse_single(line: usize, values: &[&str]) -> Result<f32> {
+    match values {
+        [value] => obj::parse_float(line, value),
+        _ => Err((line, format!("Expected one value, found {}", values.len()))),
+    }
+}
+
+/// The file name of a texture map and its addressing mode. Options other
+/// than `-clamp` are skipped together with their arguments.
+fn parse_map(line: usize, values: &[&str]) -> Result<(String, Wrap)> {
+    let mut wrap = Wrap::Repeat;
+    let mut rest = values;
+    while let [option, tail @ ..] = rest
+        && option.starts_with('-')
+    {
+        let arguments = match *option {
+            "-clamp" => {
+                wrap = match tail.first() {
+                    Some(&"on") => Wrap::Clamp,
+                    Some(&"off") => Wrap::Repeat,
+                    _ => return Err((line, "-clamp needs on or off".to_string())),
+                };
+                1
+            }
+            // up to three numbers
+            "-o" | "-s" | "-t" => tail.iter().take(3).take_while(|value| value.parse::<f32>().is_ok()).count(),
+            "-mm" => 2,
+            "-bm" | "-blendu" | "-blendv" | "-cc" | "-boost" | "-texres" | "-imfchan" => 1,
+            _ => return Err((line, format!("Unknown texture option {option}"))),
+        };
+        if tail.len() < arguments {
+            return Err((line, format!("{option} needs {arguments} values")))
+        }
+        rest = &tail[arguments..];
+    }
+    if rest.is_empty() {
+        return Err((line, "A texture map needs a file name".to_string()))
+    }
+    // the name may contain spaces
+    Ok((rest.join(" "), wrap))
+}
+
+/// Either three components or a single grey value. Spectral and CIE XYZ
+/// colors are not supported.
+fn parse_color(line: usize, values: &[&str]) -> Result<Color> {
+    match values.len() {
+        1 => {
+            let value = obj::parse_float(line, values[0])?;
+            Ok(Color::new(value, value, value))
+        }
+        3 => {
+            let [red, green, blue] = obj::parse_floats(line, values)?;
+            Ok(Color::new(red, green, blue))
+        }
+        _ => Err((line, format!("A color needs 3 values, found {}", values.len()))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LIBRARY: &str = "
+# Blender MTL File
+newmtl Plastic
+Ns 250.000000
+Ka 1.000000 1.000000 1.000000
+Kd 0.8 0.1 0.1
+Ks 0.500000 0.500000 0.500000
+Ke 0 0 0
+Ni 1.450000
+d 1.000000
+illum 2
+
+newmtl Lamp
+Kd 0.5
+Ke 4 4 4
+illum 1
+
+newmtl Mirror
+Ks 0.9 0.9 0.9
+illum 3
+
+newmtl Glass
+Kd 0.8 0.8 0.8
+Tf 0.9 1 0.9
+Ni 1.52
+illum 7
+
+newmtl Brushed
+Kd 0.9 0.6 0.2
+Ns 900
+Pr 0.35
+Pm 1
+Pc 0.5
+illum 2
+";
+
+    #[test]
+    fn maps_fields_and_illumination_models() {
+        let materials = parse(LIBRARY, Path::new("")).unwrap();
+
+        let plastic = &materials["Plastic"];
+        assert!(plastic.material_kind == MaterialKind::Plastic);
+        assert!(plastic.color.red == 0.8 && plastic.color.green == 0.1);
+        assert!(plastic.specular.blue == 0.5);
+        assert!((plastic.roughness - 0.473).abs() < 1e-3);
+        assert!(plastic.ior == 1.45);
+
+        let lamp = &materials["Lamp"];
+        assert!(lamp.material_kind == MaterialKind::Diffuse);
+        assert!(lamp.color.green == 0.5);
+        assert!(lamp.emission.red == 4.);
+
+        let mirror = &materials["Mirror"];
+        assert!(mirror.material_kind == MaterialKind::Glossy);
+        assert!(mirror.color.red == 0.9);
+
+        let glass = &materials["Glass"];
+        assert!(glass.material_kind == MaterialKind::Dielectric);
+        assert!(glass.color.red == 0.9 && glass.color.green == 1.);
+        assert!(glass.ior == 1.52);
+
+        let brushed = &materials["Brushed"];
+        assert!(brushed.material_kind == MaterialKind::Principled);
+        assert!(brushed.color.green == 0.6);
+        assert!(brushed.roughness == 0.35);
+        assert!(brushed.principled.metallic == 1. && brushed.principled.clearcoat == 0.5);
+        assert!(brushed.principled.specular == 0.5);
+    }
+
+    #[test]
+    fn texture_map_options() {
+        let (file, wrap) = parse_map(1, &["-s", "2", "2", "-clamp", "on", "-bm", "0.5", "wood", "grain.png"]).unwrap();
+        assert!(file == "wood grain.png");
+        assert!(wrap == Wrap::Clamp);
+
+        let (file, wrap) = parse_map(1, &["-o", "0.5", "albedo.tga"]).unwrap();
+        assert!(file == "albedo.tga" && wrap == Wrap::Repeat);
+
+        assert!(parse_map(1, &["-clamp", "on"]).is_err());
+        assert!(parse_map(1, &["-halo", "albedo.png"]).is_err());
+    }
+
+    #[test]
+    fn errors_point_at_lines() {
+        assert!(parse("Kd 1 1 1\n", Path::new("")).unwrap_err().0 == 1);
+        assert!(parse("newmtl a\nKd 1 1\n", Path::new("")).unwrap_err().0 == 2);
+        assert!(parse("newmtl a\n\nillum 1.5\n", Path::new("")).unwrap_err().0 == 3);
+        assert!(parse("newmtl a\nPm 1.5\n", Path::new("")).unwrap_err().0 == 2);
+    }
+}
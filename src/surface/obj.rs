@@ -0,0 +1,263 @@
+//! Wavefront OBJ reader. Only polygonal geometry is read, curves, lines and
+//! points are skipped together with every other unsupported statement.
+
+use std::fmt;
+use std::fs::read_to_string;
+use std::path::{Path, PathBuf};
+
+use crate::primitives::vec3d::{Point3d, Vec3d};
+
+/// Zero-based indices of one corner of a face.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct VertexIndex {
+    pub position: usize,
+    pub texture: Option<usize>,
+    pub normal: Option<usize>,
+}
+
+/// A triangle and the index of the material that was in use for it.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct Face {
+    pub vertices: [VertexIndex; 3],
+    /// Index into `ObjData::material_names`.
+    pub material: Option<usize>,
+}
+
+/// Polygonal geometry of an OBJ file with all faces split into triangles.
+#[derive(Debug, Default)]
+pub struct ObjData {
+    pub positions: Vec<Point3d>,
+    pub texture_coordinates: Vec<(f32, f32)>,
+    pub normals: Vec<Vec3d>,
+    pub faces: Vec<Face>,
+    /// MTL files as they are written in the OBJ file.
+    pub material_libraries: Vec<String>,
+    pub material_names: Vec<String>,
+}
+
+/// Error of an OBJ or MTL file.
+#[derive(Debug)]
+pub struct ObjError {
+    pub path: PathBuf,
+    /// Line of the statement, `None` if the file could not be read at all.
+    pub line: Option<usize>,
+    pub message: String,
+}
+
+impl fmt::Display for ObjError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(f, "{}:{}: {}", self.path.display(), line, self.message),
+            None => write!(f, "{}: {}", self.path.display(), self.message),
+        }
+    }
+}
+
+impl std::error::Error for ObjError {}
+
+pub(super) type Result<T> = std::result::Result<T, (usize, String)>;
+
+pub fn load(path: &Path) -> std::result::Result<ObjData, ObjError> {
+    read_file(path, parse)
+}
+
+/// Reads and parses a file, adding the path to any error.
+pub(super) fn read_file<T>(path: &Path, parse: impl Fn(&str) -> Result<T>) -> std::result::Result<T, ObjError> {
+    let content = read_to_string(path).map_err(|e| ObjError {
+        path: path.to_path_buf(),
+        line: None,
+        message: e.to_string(),
+    })?;
+    parse(&content).map_err(|(line, message)| ObjError {
+        path: path.to_path_buf(),
+        line: Some(line),
+        message,
+    })
+}
+
+/// Parses the content of an OBJ file. Errors carry the line they occurred on.
+pub fn parse(content: &str) -> Result<ObjData> {
+    let mut data = ObjData::default();
+    let mut material = None;
+    for (line, statement) in statements(content) {
+        let mut words = statement.split_whitespace();
+        let Some(keyword) = words.next() else {
+            continue
+        };
+        let values: Vec<&str> = words.collect();
+        match keyword {
+            "v" => {
+                // an optional weight or, as some exporters do, a vertex color may follow
+                if ![3, 4, 6].contains(&values.len()) {
+                    return Err((line, format!("A vertex needs 3 coordinates, found {}", values.len())))
+                }
+                let [x, y, z] = parse_floats(line, &values[..3])?;
+                data.positions.push(Point3d::new(x, y, z));
+            }
+            "vt" => {
+                if !(1..=3).contains(&values.len()) {
+                    return Err((line, format!("A texture coordinate needs 1 to 3 values, found {}", values.len())))
+                }
+                let u = parse_float(line, values[0])?;
+                let v = match values.get(1) {
+                    Some(value) => parse_float(line, value)?,
+                    None => 0.,
+                };
+                data.texture_coordinates.push((u, v));
+            }
+            "vn" => {
+                if values.len() != 3 {
+                    return Err((line, format!("A normal needs 3 coordinates, found {}", values.len())))
+                }
+                let [x, y, z] = parse_floats(line, &values)?;
+                data.normals.push(Vec3d::new(x, y, z));
+            }
+            "f" => {
+                if values.len() < 3 {
+                    return Err((line, format!("A face needs at least 3 vertices, found {}", values.len())))
+                }
+                let corners = values
+                    .iter()
+                    .map(|value| parse_vertex(line, value, &data))
+                    .collect::<Result<Vec<VertexIndex>>>()?;
+                // fan triangulation, exact for the convex polygons exporters write
+                for i in 1..corners.len() - 1 {
+                    data.faces.push(Face {
+                        vertices: [corners[0], corners[i], corners[i + 1]],
+                        material,
+                    });
+                }
+            }
+            "mtllib" => data.material_libraries.extend(values.iter().map(|value| value.to_string())),
+            "usemtl" => {
+                if values.is_empty() {
+                    return Err((line, "usemtl needs a material name".to_string()))
+                }
+                let name = values.join(" ");
+                material = match data.material_names.iter().position(|known| *known == name) {
+                    Some(index) => Some(index),
+                    None => {
+                        data.material_names.push(name);
+                        Some(data.material_names.len() - 1)
+                    }
+                };
+            }
+            _ => {} // ignore other statements
+        }
+    }
+    Ok(data)
+}
+
+/// Logical lines with comments removed and continuations joined,
+/// each with the number of the line it starts on.
+pub(super) fn statements(content: &str) -> Vec<(usize, String)> {
+    let mut statements = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+    for (index, line) in content.lines().enumerate() {
+        let text = line.split('#').next().unwrap_or_default().trim_end();
+        let (text, continues) = match text.strip_suffix('\\') {
+            Some(text) => (text, true),
+            None => (text, false),
+        };
+        let (_, statement) = current.get_or_insert_with(|| (index + 1, String::new()));
+        statement.push(' ');
+        statement.push_str(text);
+        if !continues {
+            statements.extend(current.take());
+        }
+    }
+    statements.extend(current);
+    statements
+}
+
+pub(super) fn parse_float(line: usize, value: &str) -> Result<f32> {
+    value.parse().map_err(|_| (line, format!("Invalid number {value}")))
+}
+
+pub(super) fn parse_floats<const N: usize>(line: usize, values: &[&str]) -> Result<[f32; N]> {
+    let mut floats = [0.; N];
+    for (float, value) in floats.iter_mut().zip(values) {
+        *float = parse_float(line, value)?;
+    }
+    Ok(floats)
+}
+
+/// One corner of a face in the `v`, `v/vt`, `v//vn` or `v/vt/vn` form.
+fn parse_vertex(line: usize, value: &str, data: &ObjData) -> Result<VertexIndex> {
+    let mut parts = value.split('/');
+    let position = parts.next().unwrap_or_default();
+    let texture = parts.next().filter(|part| !part.is_empty());
+    let normal = parts.next().filter(|part| !part.is_empty());
+    if parts.next().is_some() {
+        return Err((line, format!("Invalid face vertex {value}")))
+    }
+    Ok(VertexIndex {
+        position: resolve_index(line, position, data.positions.len())?,
+        texture: texture.map(|i| resolve_index(line, i, data.texture_coordinates.len())).transpose()?,
+        normal: normal.map(|i| resolve_index(line, i, data.normals.len())).transpose()?,
+    })
+}
+
+/// Turns a one-based or a negative, relative to the end, index into a zero-based one.
+fn resolve_index(line: usize, value: &str, count: usize) -> Result<usize> {
+    let index: i64 = value.parse().map_err(|_| (line, format!("Invalid index {value}")))?;
+    let resolved = if index < 0 { count as i64 + index } else { index - 1 };
+    if index == 0 || resolved < 0 || resolved >= count as i64 {
+        return Err((line, format!("Index {index} is out of range, there are {count} elements")))
+    }
+    Ok(resolved as usize)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn whitespace_comments_and_continuations() {
+        let data = parse("# a triangle\n v\t0 0 0 1.0\nv  1 0 0 # weight is optional\nv 0 1 \\\n 0\n\nf 1 2 3\n").unwrap();
+
+        assert!(data.positions.len() == 3);
+        assert!(data.positions[2] == Point3d::new(0., 1., 0.));
+        assert!(data.faces.len() == 1);
+    }
+
+    #[test]
+    fn vertex_forms_and_negative_indices() {
+        let content = "v 0 0 0\nv 1 0 0\nv 0 1 0\nvt 0 0\nvt 1 0\nvn 0 0 1\nf -3/1/-1 -2//1 -1/2\n";
+        let data = parse(content).unwrap();
+
+        let [a, b, c] = data.faces[0].vertices;
+        assert!(a == VertexIndex { position: 0, texture: Some(0), normal: Some(0) });
+        assert!(b == VertexIndex { position: 1, texture: None, normal: Some(0) });
+        assert!(c == VertexIndex { position: 2, texture: Some(1), normal: None });
+    }
+
+    #[test]
+    fn polygons_are_fanned() {
+        let data = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nv 0 1 0\nv -1 1 0\nf 1 2 3 4 5\n").unwrap();
+
+        assert!(data.faces.len() == 3);
+        assert!(data.faces.iter().all(|face| face.vertices[0].position == 0));
+        assert!(data.faces[2].vertices[2].position == 4);
+    }
+
+    #[test]
+    fn faces_remember_their_material() {
+        let content = "mtllib a.mtl b.mtl\nv 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 3\nusemtl red\nf 1 2 3\nusemtl blue\nf 1 2 3\nusemtl red\nf 1 2 3\n";
+        let data = parse(content).unwrap();
+
+        assert!(data.material_libraries == ["a.mtl", "b.mtl"]);
+        assert!(data.material_names == ["red", "blue"]);
+        let materials: Vec<Option<usize>> = data.faces.iter().map(|face| face.material).collect();
+        assert!(materials == [None, Some(0), Some(1), Some(0)]);
+    }
+
+    #[test]
+    fn errors_point_at_lines() {
+        assert!(parse("v 0 0 0\nv 1 0 x\n").unwrap_err().0 == 2);
+        assert!(parse("v 0 0 0\nv 1 0 0\n\nf 1 2\n").unwrap_err().0 == 4);
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 1 2 4\n").unwrap_err().0 == 4);
+        assert!(parse("v 0 0 0\nv 1 0 0\nv 0 1 0\nf 0 1 2\n").unwrap_err().0 == 4);
+        assert!(parse("v 0 \\\n0 0\nv 0 0 0 0 0\n").unwrap_err().0 == 3);
+    }
+}
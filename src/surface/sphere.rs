@@ -1,8 +1,11 @@
+use std::f32::consts::{PI, TAU};
+
 use crate::primitives::vec3d::{Point3d, Vec3d};
 use crate::primitives::Ray;
 
+use super::bounding_box::BoundingBox;
 use super::material::Material;
-use super::Surface;
+use super::{HitRecord, Hittable};
 
 #[derive(Debug)]
 pub struct Sphere {
@@ -17,12 +20,8 @@ impl Sphere {
     }
 }
 
-impl Surface for Sphere {
-    fn get_normal(&self, point: Point3d) -> Vec3d {
-        return (point - self.center) / self.radius;
-    }
-
-    fn intersect(&self, ray: &Ray) -> Option<f32> {
+impl Hittable for Sphere {
+    fn intersect(&self, ray: &Ray) -> Option<HitRecord<'_>> {
         let oc = ray.origin - self.center;
     
         let k1 = ray.direction * ray.direction;
@@ -36,18 +35,74 @@ impl Surface for Sphere {
     
         let discr_sqrt = discr.sqrt();
 
-        let t1 = (-k2 + discr_sqrt) / (2. * k1);
-        let t2 = (-k2 - discr_sqrt) / (2. * k1);
+        let t1 = (-k2 - discr_sqrt) / (2. * k1);
+        let t2 = (-k2 + discr_sqrt) / (2. * k1);
     
-        //TODO could do this check earlier to optimize
-        if t1 < 0.001 && t2 < 0.001 {
-            return None
+        // the far side counts when the ray starts inside
+        let t = if t1 >= 0.001 { t1 } else if t2 >= 0.001 { t2 } else { return None };
+        let point = ray.origin + ray.direction * t;
+        let normal = (point - self.center) / self.radius;
+        let hit = HitRecord {
+            t,
+            point,
+            geometric_normal: normal,
+            shading_normal: normal,
+            uv: spherical_uv(normal),
+            barycentrics: (0., 0.),
+            front_face: true,
+            material: &self.material,
+            primitive_id: 0,
+        };
+        return Some(hit.orient(ray))
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let radius = Vec3d::new(self.radius, self.radius, self.radius);
+        BoundingBox {
+            min: self.center - radius,
+            max: self.center + radius,
         }
-    
-        return Some(f32::min(t1, t2))
     }
+}
+
+/// Longitude and latitude of a point on the unit sphere mapped to [0, 1],
+/// with the poles on the Z axis.
+fn spherical_uv(normal: Vec3d) -> (f32, f32) {
+    let u = 0.5 + normal.y.atan2(normal.x) / TAU;
+    let v = 0.5 + normal.z.clamp(-1., 1.).asin() / PI;
+    (u, v)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::Color;
+    use crate::surface::material::MaterialKind;
+
+    fn sphere() -> Sphere {
+        Sphere::new(Point3d::new(0., 0., 0.), 2., Material::new(MaterialKind::Diffuse, Color::new(1., 1., 1.)))
+    }
+
+    #[test]
+    fn hit_from_outside() {
+        let ray = Ray { origin: Point3d::new(-5., 0., 0.), direction: Vec3d::new(1., 0., 0.) };
+        let sphere = sphere();
+        let hit = sphere.intersect(&ray).unwrap();
+
+        assert!(hit.t == 3.);
+        assert!(hit.front_face);
+        assert!(hit.geometric_normal == Vec3d::new(-1., 0., 0.));
+    }
+
+    #[test]
+    fn hit_from_inside_is_back_face() {
+        let ray = Ray { origin: Point3d::new(0., 0., 0.), direction: Vec3d::new(0., 0., 1.) };
+        let sphere = sphere();
+        let hit = sphere.intersect(&ray).unwrap();
 
-    fn get_material(&self) -> Material {
-        return self.material
+        assert!(hit.t == 2.);
+        assert!(!hit.front_face);
+        assert!(hit.shading_normal == Vec3d::new(0., 0., -1.));
+        assert!((hit.uv.1 - 1.).abs() < 1e-6);
     }
 }
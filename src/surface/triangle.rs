@@ -1,54 +1,119 @@
+use std::sync::Arc;
+
 use crate::primitives::vec3d::{Point3d, Vec3d};
 use crate::primitives::Ray;
 
+use super::bounding_box::BoundingBox;
 use super::material::Material;
-use super::Surface;
+use super::{HitRecord, Hittable};
 
-#[derive(Debug, Clone, Copy)]
+#[derive(Debug, Clone)]
 pub struct Triangle {
     a: Point3d,
     b: Point3d,
     c: Point3d,
-    material: Material,
+    /// Unit normals at the corners, if the triangle is smooth shaded.
+    normals: Option<[Vec3d; 3]>,
+    /// Texture coordinates of the corners.
+    uvs: Option<[(f32, f32); 3]>,
+    material: Arc<Material>,
 }
 
 impl Triangle {
-    pub fn new(a: Point3d, b: Point3d, c: Point3d, material: Material) -> Self {
-        Self {a, b, c, material}
+    pub fn new(a: Point3d, b: Point3d, c: Point3d, material: Arc<Material>) -> Self {
+        Self {a, b, c, normals: None, uvs: None, material}
+    }
+
+    pub fn with_normals(self, normals: [Vec3d; 3]) -> Self {
+        Self { normals: Some(normals), ..self }
+    }
+
+    pub fn with_uvs(self, uvs: [(f32, f32); 3]) -> Self {
+        Self { uvs: Some(uvs), ..self }
+    }
+
+    /// Distance and barycentric coordinates of the hit. Cheaper than
+    /// `intersect` while searching for the closest of many triangles.
+    pub fn intersect_barycentric(&self, ray: &Ray) -> Option<(f32, f32, f32)> {
+        moller_trumbore_intersection(ray, self)
     }
-}
 
-impl Surface for Triangle {
-    fn get_normal(&self, point: Point3d) -> Vec3d {
+    /// Completes a hit found by `intersect_barycentric`.
+    pub fn hit_record(&self, ray: &Ray, t: f32, u: f32, v: f32, primitive_id: usize) -> HitRecord<'_> {
+        let hit = HitRecord {
+            t,
+            point: ray.origin + ray.direction * t,
+            geometric_normal: self.face_normal(),
+            shading_normal: self.shading_normal(u, v),
+            uv: self.uv(u, v),
+            barycentrics: (u, v),
+            front_face: true,
+            material: &self.material,
+            primitive_id,
+        };
+        hit.orient(ray)
+    }
+
+    /// Interpolated texture coordinates, or the barycentrics if there are none.
+    fn uv(&self, u: f32, v: f32) -> (f32, f32) {
+        match self.uvs {
+            Some([a, b, c]) => {
+                let w = 1. - u - v;
+                (a.0 * w + b.0 * u + c.0 * v, a.1 * w + b.1 * u + c.1 * v)
+            }
+            None => (u, v),
+        }
+    }
+
+    fn face_normal(&self) -> Vec3d {
         let a_b = self.b - self.a;
         let b_c = self.c - self.b;
         return a_b.cross(&b_c).normalize()
     }
 
-    fn intersect(&self, ray: &Ray) -> Option<f32> {
-        moller_trumbore_intersection(ray, &self)
+    /// Normal interpolated from the corners, or the face normal of a flat triangle.
+    fn shading_normal(&self, u: f32, v: f32) -> Vec3d {
+        match self.normals {
+            Some([a, b, c]) => (a * (1. - u - v) + b * u + c * v).normalize(),
+            None => self.face_normal(),
+        }
     }
-    
-    fn get_material(&self) -> Material {
-        return self.material
+}
+
+impl Hittable for Triangle {
+    fn intersect(&self, ray: &Ray) -> Option<HitRecord<'_>> {
+        let (t, u, v) = moller_trumbore_intersection(ray, self)?;
+        Some(self.hit_record(ray, t, u, v, 0))
+    }
+
+    fn bounding_box(&self) -> BoundingBox {
+        let mut bounding_box = BoundingBox::empty();
+        bounding_box.grow(self.a);
+        bounding_box.grow(self.b);
+        bounding_box.grow(self.c);
+        bounding_box
+    }
+
+    fn occluded(&self, ray: &Ray, t_max: f32) -> bool {
+        moller_trumbore_intersection(ray, self).is_some_and(|(t, _, _)| t < t_max)
     }
 }
 
-fn moller_trumbore_intersection (ray: &Ray, triangle: &Triangle) -> Option<f32> {
+fn moller_trumbore_intersection (ray: &Ray, triangle: &Triangle) -> Option<(f32, f32, f32)> {
     let e1 = triangle.b - triangle.a;
     let e2 = triangle.c - triangle.a;
 
     let ray_cross_e2 = ray.direction.cross(&e2);
     let det = e1.dot(&ray_cross_e2);
 
-    if det < f32::EPSILON {
-        return None; // This ray does not face the triangle.
+    if det.abs() < f32::EPSILON {
+        return None; // This ray is parallel to the triangle.
     }
 
     let inv_det = 1.0 / det;
     let s = ray.origin - triangle.a;
     let u = inv_det * s.dot(&ray_cross_e2);
-    if u < 0.0 || u > 1.0 {
+    if !(0.0..=1.0).contains(&u) {
         return None;
     }
 
@@ -61,10 +126,46 @@ fn moller_trumbore_intersection (ray: &Ray, triangle: &Triangle) -> Option<f32>
     let t = inv_det * e2.dot(&s_cross_e1);
 
     if t > f32::EPSILON { // ray intersection
-        return Some(t)
+        return Some((t, u, v))
     }
     else
     {
         return None
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::primitives::Color;
+    use crate::surface::material::MaterialKind;
+
+    // counterclockwise seen from above, so the face normal points up
+    fn triangle() -> Triangle {
+        let material = Arc::new(Material::new(MaterialKind::Diffuse, Color::new(1., 1., 1.)));
+        Triangle::new(Point3d::new(0., 0., 0.), Point3d::new(1., 0., 0.), Point3d::new(0., 1., 0.), material)
+    }
+
+    #[test]
+    fn hit_from_the_front() {
+        let ray = Ray { origin: Point3d::new(0.25, 0.25, 1.), direction: Vec3d::new(0., 0., -1.) };
+        let triangle = triangle();
+        let hit = triangle.intersect(&ray).unwrap();
+
+        assert!(hit.t == 1.);
+        assert!(hit.front_face);
+        assert!(hit.geometric_normal == Vec3d::new(0., 0., 1.));
+    }
+
+    #[test]
+    fn hit_from_behind_is_back_face() {
+        let ray = Ray { origin: Point3d::new(0.25, 0.25, -1.), direction: Vec3d::new(0., 0., 1.) };
+        let triangle = triangle();
+        let hit = triangle.intersect(&ray).unwrap();
+
+        assert!(hit.t == 1.);
+        assert!(!hit.front_face);
+        assert!(hit.geometric_normal == Vec3d::new(0., 0., -1.));
+        assert!(triangle.occluded(&ray, 2.));
+    }
+}
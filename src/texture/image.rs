@@ -0,0 +1,327 @@
+use std::fs;
+use std::path::Path;
+
+use crate::primitives::vec3d::Point3d;
+use crate::primitives::Color;
+use crate::tonemap::srgb_to_linear;
+
+use super::Texture;
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Filter {
+    Nearest,
+    Bilinear,
+}
+
+/// What happens to texture coordinates outside of [0, 1].
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Wrap {
+    Repeat,
+    Clamp,
+}
+
+/// A texture backed by an image. Pixels are kept as linear colors, with
+/// `v` = 0 at the bottom row of the image.
+#[derive(Debug)]
+pub struct ImageTexture {
+    width: usize,
+    height: usize,
+    pixels: Vec<Color>,
+    pub filter: Filter,
+    pub wrap: Wrap,
+}
+
+impl ImageTexture {
+    /// `pixels` go row by row from the top, as 8-bit sRGB values.
+    pub fn from_srgb8(width: usize, height: usize, pixels: &[[u8; 3]]) -> ImageTexture {
+        assert!(width > 0 && height > 0 && pixels.len() == width * height, "Invalid image size!");
+        let to_linear = |value: u8| srgb_to_linear(value as f32 / 255.);
+        ImageTexture {
+            width,
+            height,
+            pixels: pixels.iter().map(|&[r, g, b]| Color::new(to_linear(r), to_linear(g), to_linear(b))).collect(),
+            filter: Filter::Bilinear,
+            wrap: Wrap::Repeat,
+        }
+    }
+
+    /// Reads a PNG, PPM or TGA image, chosen by the file extension.
+    pub fn load(path: &Path) -> Result<ImageTexture, String> {
+        let bytes = fs::read(path).map_err(|e| format!("Could not read {}: {}", path.display(), e))?;
+        let extension = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .unwrap_or_default();
+        let decoded = match extension.as_str() {
+            "png" => decode_png(&bytes),
+            "ppm" => decode_ppm(&bytes),
+            "tga" => decode_tga(&bytes),
+            _ => Err("Unsupported image format, use png, ppm or tga".to_string()),
+        };
+        decoded.map_err(|e| format!("{}: {}", path.display(), e))
+    }
+
+    fn texel(&self, x: i64, y: i64) -> Color {
+        let (x, y) = match self.wrap {
+            Wrap::Repeat => (x.rem_euclid(self.width as i64), y.rem_euclid(self.height as i64)),
+            Wrap::Clamp => (x.clamp(0, self.width as i64 - 1), y.clamp(0, self.height as i64 - 1)),
+        };
+        self.pixels[y as usize * self.width + x as usize]
+    }
+}
+
+impl Texture for ImageTexture {
+    fn value(&self, (u, v): (f32, f32), _point: Point3d) -> Color {
+        // folding the coordinates into the image first keeps huge ones from
+        // overflowing the pixel indices, NaN and infinity are read at 0
+        let finite = |t: f32| if t.is_finite() { t } else { 0. };
+        let (u, v) = (finite(u), finite(v));
+        let (u, v) = match self.wrap {
+            Wrap::Repeat => (u.rem_euclid(1.), v.rem_euclid(1.)),
+            Wrap::Clamp => (u.clamp(0., 1.), v.clamp(0., 1.)),
+        };
+        // continuous pixel coordinates, texel centers are at .5
+        let x = u * self.width as f32;
+        let y = (1. - v) * self.height as f32;
+        match self.filter {
+            Filter::Nearest => self.texel(x.floor() as i64, y.floor() as i64),
+            Filter::Bilinear => {
+                let (x, y) = (x - 0.5, y - 0.5);
+                let (x0, y0) = (x.floor(), y.floor());
+                let (fx, fy) = (x - x0, y - y0);
+                let (x0, y0) = (x0 as i64, y0 as i64);
+                let top = self.texel(x0, y0) * (1. - fx) + self.texel(x0 + 1, y0) * fx;
+                let bottom = self.texel(x0, y0 + 1) * (1. - fx) + self.texel(x0 + 1, y0 + 1) * fx;
+                top * (1. - fy) + bottom * fy
+            }
+        }
+    }
+}
+
+fn decode_png(bytes: &[u8]) -> Result<ImageTexture, String> {
+    let mut decoder = png::Decoder::new(bytes);
+    // palettes, low bit depths and 16 bits all end up as 8-bit channels
+    decoder.set_transformations(png::Transformations::EXPAND | png::Transformations::STRIP_16);
+    let mut reader = decoder.read_info().map_err(|e| e.to_string())?;
+    let mut buffer = vec![0; reader.output_buffer_size()];
+    let info = reader.next_frame(&mut buffer).map_err(|e| e.to_string())?;
+    let channels = info.color_type.samples();
+    let pixels: Vec<[u8; 3]> = buffer[..info.buffer_size()]
+        .chunks_exact(channels)
+        .map(|pixel| match channels {
+            // grey, possibly with alpha
+            1 | 2 => [pixel[0]; 3],
+            _ => [pixel[0], pixel[1], pixel[2]],
+        })
+        .collect();
+    Ok(ImageTexture::from_srgb8(info.width as usize, info.height as usize, &pixels))
+}
+
+/// Binary (P6) or ASCII (P3) PPM.
+fn decode_ppm(bytes: &[u8]) -> Result<ImageTexture, String> {
+    let mut position = 0;
+    let mut header = Vec::new();
+    while header.len() < 4 {
+        let token = next_token(bytes, &mut position).ok_or("Truncated PPM header")?;
+        header.push(token);
+    }
+    let number = |token: &str| token.parse::<usize>().map_err(|_| format!("Invalid PPM header value {token}"));
+    let (width, height, max) = (number(&header[1])?, number(&header[2])?, number(&header[3])?);
+    if width == 0 || height == 0 || !(1..=65535).contains(&max) {
+        return Err("Invalid PPM header".to_string())
+    }
+    let count = width.checked_mul(height).and_then(|n| n.checked_mul(3)).ok_or("Invalid PPM header")?;
+    let values: Vec<usize> = match header[0].as_str() {
+        "P6" => {
+            // a single whitespace separates the header from the data
+            let data = bytes.get(position + 1..).unwrap_or_default();
+            let size = if max < 256 { 1 } else { 2 };
+            if data.len() / size < count {
+                return Err("Truncated PPM data".to_string())
+            }
+            data.chunks_exact(size)
+                .take(count)
+                .map(|value| value.iter().fold(0, |acc, &byte| acc * 256 + byte as usize))
+                .collect()
+        }
+        "P3" => (0..count)
+            .map(|_| next_token(bytes, &mut position).ok_or("Truncated PPM data".to_string()).and_then(|t| number(&t)))
+            .collect::<Result<_, _>>()?,
+        other => return Err(format!("Unsupported PPM type {other}")),
+    };
+    let pixels: Vec<[u8; 3]> = values
+        .chunks_exact(3)
+        .map(|rgb| [0, 1, 2].map(|i| (rgb[i].min(max) * 255 / max) as u8))
+        .collect();
+    Ok(ImageTexture::from_srgb8(width, height, &pixels))
+}
+
+/// Next whitespace separated token of a PPM header, skipping comments.
+fn next_token(bytes: &[u8], position: &mut usize) -> Option<String> {
+    loop {
+        match bytes.get(*position)? {
+            b'#' => {
+                while bytes.get(*position).is_some_and(|&byte| byte != b'\n') {
+                    *position += 1;
+                }
+            }
+            byte if byte.is_ascii_whitespace() => *position += 1,
+            _ => break,
+        }
+    }
+    let start = *position;
+    while bytes.get(*position).is_some_and(|byte| !byte.is_ascii_whitespace()) {
+        *position += 1;
+    }
+    Some(String::from_utf8_lossy(&bytes[start..*position]).into_owned())
+}
+
+/// Uncompressed or run-length encoded true color and greyscale TGA.
+fn decode_tga(bytes: &[u8]) -> Result<ImageTexture, String> {
+    let header = bytes.get(..18).ok_or("Truncated TGA header")?;
+    let image_type = header[2];
+    let width = u16::from_le_bytes([header[12], header[13]]) as usize;
+    let height = u16::from_le_bytes([header[14], header[15]]) as usize;
+    let bits = header[16];
+    let top_down = header[17] & 0x20 != 0;
+    if header[1] != 0 || ![2, 3, 10, 11].contains(&image_type) {
+        return Err(format!("Unsupported TGA image type {image_type}"))
+    }
+    let size = match (image_type, bits) {
+        (2 | 10, 24) => 3,
+        (2 | 10, 32) => 4,
+        (3 | 11, 8) => 1,
+        _ => return Err(format!("Unsupported TGA pixel depth {bits}")),
+    };
+    if width == 0 || height == 0 {
+        return Err("Empty TGA image".to_string())
+    }
+
+    let mut data = bytes.get(18 + header[0] as usize..).ok_or("Truncated TGA data")?;
+    let count = width * height;
+    // the header alone can't be trusted with the allocation
+    let mut raw: Vec<&[u8]> = Vec::with_capacity(count.min(data.len()));
+    let truncated = || "Truncated TGA data".to_string();
+    if image_type < 10 {
+        raw.extend(data.chunks_exact(size).take(count));
+    } else {
+        while raw.len() < count {
+            let (&packet, rest) = data.split_first().ok_or_else(truncated)?;
+            let length = (packet & 0x7f) as usize + 1;
+            if packet & 0x80 != 0 {
+                let pixel = rest.get(..size).ok_or_else(truncated)?;
+                raw.extend(std::iter::repeat_n(pixel, length));
+                data = &rest[size..];
+            } else {
+                let pixels = rest.get(..length * size).ok_or_else(truncated)?;
+                raw.extend(pixels.chunks_exact(size));
+                data = &rest[length * size..];
+            }
+        }
+        raw.truncate(count);
+    }
+    if raw.len() < count {
+        return Err(truncated())
+    }
+
+    let mut pixels: Vec<[u8; 3]> = raw
+        .iter()
+        .map(|pixel| match size {
+            1 => [pixel[0]; 3],
+            _ => [pixel[2], pixel[1], pixel[0]],
+        })
+        .collect();
+    if !top_down {
+        let rows: Vec<&[[u8; 3]]> = pixels.chunks(width).rev().collect();
+        pixels = rows.concat();
+    }
+    Ok(ImageTexture::from_srgb8(width, height, &pixels))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const ORIGIN: Point3d = Point3d { x: 0., y: 0., z: 0. };
+
+    // black on the left, white on the right
+    fn texture() -> ImageTexture {
+        ImageTexture::from_srgb8(2, 1, &[[0, 0, 0], [255, 255, 255]])
+    }
+
+    #[test]
+    fn nearest_and_bilinear() {
+        let mut texture = texture();
+        texture.filter = Filter::Nearest;
+        assert!(texture.value((0.3, 0.5), ORIGIN).red == 0.);
+        assert!(texture.value((0.7, 0.5), ORIGIN).red == 1.);
+
+        texture.filter = Filter::Bilinear;
+        assert!((texture.value((0.5, 0.5), ORIGIN).red - 0.5).abs() < 1e-6);
+    }
+
+    #[test]
+    fn repeat_and_clamp() {
+        let mut texture = texture();
+        texture.filter = Filter::Nearest;
+        assert!(texture.value((1.3, 0.5), ORIGIN).red == 0.);
+        assert!(texture.value((-0.3, 0.5), ORIGIN).red == 1.);
+
+        texture.wrap = Wrap::Clamp;
+        assert!(texture.value((1.3, 0.5), ORIGIN).red == 1.);
+        assert!(texture.value((-0.3, 0.5), ORIGIN).red == 0.);
+    }
+
+    #[test]
+    fn extreme_coordinates() {
+        let mut texture = texture();
+        for wrap in [Wrap::Repeat, Wrap::Clamp] {
+            texture.wrap = wrap;
+            for uv in [(f32::INFINITY, 0.5), (f32::NAN, f32::NEG_INFINITY), (1e30, -1e30)] {
+                assert!(texture.value(uv, ORIGIN).red.is_finite());
+            }
+        }
+    }
+
+    #[test]
+    fn ppm_binary_and_ascii() {
+        let binary = decode_ppm(b"P6\n# comment\n2 1\n255\n\x00\x00\x00\xff\xff\xff").unwrap();
+        let ascii = decode_ppm(b"P3 2 1 15\n0 0 0\n15 15 15\n").unwrap();
+
+        for texture in [binary, ascii] {
+            assert!(texture.width == 2 && texture.height == 1);
+            assert!(texture.pixels[1].green == 1.);
+        }
+    }
+
+    #[test]
+    fn ppm_size_overflow() {
+        let error = decode_ppm(b"P6 4294967296 4294967296 255\n").err().unwrap();
+        assert!(error == "Invalid PPM header");
+    }
+
+    #[test]
+    fn tga_bottom_up_and_rle() {
+        let mut header = vec![0, 0, 10, 0, 0, 0, 0, 0, 0, 0, 0, 0, 1, 0, 2, 0, 24, 0];
+        // a run of one blue pixel, then a raw packet with one red pixel
+        header.extend([0x80, 255, 0, 0, 0x00, 0, 0, 255]);
+        let texture = decode_tga(&header).unwrap();
+
+        // the first row in the file is the bottom one
+        assert!(texture.pixels[0].red == 1. && texture.pixels[0].blue == 0.);
+        assert!(texture.pixels[1].blue == 1.);
+    }
+
+    #[test]
+    fn png_roundtrip() {
+        let mut data = Vec::new();
+        let mut encoder = png::Encoder::new(&mut data, 1, 2);
+        encoder.set_color(png::ColorType::Grayscale);
+        encoder.write_header().unwrap().write_image_data(&[255, 0]).unwrap();
+        let texture = decode_png(&data).unwrap();
+
+        assert!(texture.width == 1 && texture.height == 2);
+        assert!(texture.pixels[0].green == 1. && texture.pixels[1].green == 0.);
+    }
+}
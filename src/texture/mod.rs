@@ -0,0 +1,14 @@
+use std::fmt::Debug;
+
+use crate::primitives::vec3d::Point3d;
+use crate::primitives::Color;
+
+pub mod image;
+pub mod noise;
+pub mod procedural;
+
+/// A color that varies over a surface.
+pub trait Texture: Debug + Send + Sync {
+    /// Color at the texture coordinates `uv` of the hit at `point`.
+    fn value(&self, uv: (f32, f32), point: Point3d) -> Color;
+}
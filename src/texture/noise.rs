@@ -0,0 +1,111 @@
+use crate::primitives::vec3d::Point3d;
+
+/// Ken Perlin's improved gradient noise with a seeded permutation.
+#[derive(Debug, Clone)]
+pub struct Perlin {
+    /// The permutation twice over, so lookups of `i + 1` need no wrapping.
+    permutation: [u8; 512],
+}
+
+impl Perlin {
+    /// Finer octaves than this add nothing visible at single precision.
+    pub const MAX_OCTAVES: u32 = 16;
+
+    pub fn new(seed: u64) -> Perlin {
+        let mut shuffled: Vec<u8> = (0..=255).collect();
+        fastrand::Rng::with_seed(seed).shuffle(&mut shuffled);
+        let mut permutation = [0; 512];
+        for (i, value) in permutation.iter_mut().enumerate() {
+            *value = shuffled[i % 256];
+        }
+        Perlin { permutation }
+    }
+
+    /// Smooth noise in about [-1, 1], zero at every integer point.
+    pub fn noise(&self, point: Point3d) -> f32 {
+        let (x0, y0, z0) = (point.x.floor(), point.y.floor(), point.z.floor());
+        let (x, y, z) = (point.x - x0, point.y - y0, point.z - z0);
+        let (xi, yi, zi) = (x0 as i64 & 255, y0 as i64 & 255, z0 as i64 & 255);
+        let hash = |i: i64, j: i64, k: i64| {
+            let p = |n: i64| self.permutation[n as usize] as i64;
+            p(p(p(xi + i) + yi + j) + zi + k)
+        };
+        let corner = |i: i64, j: i64, k: i64| gradient(hash(i, j, k), x - i as f32, y - j as f32, z - k as f32);
+        let (u, v, w) = (fade(x), fade(y), fade(z));
+        let lerp = |t: f32, a: f32, b: f32| a + t * (b - a);
+        lerp(
+            w,
+            lerp(v, lerp(u, corner(0, 0, 0), corner(1, 0, 0)), lerp(u, corner(0, 1, 0), corner(1, 1, 0))),
+            lerp(v, lerp(u, corner(0, 0, 1), corner(1, 0, 1)), lerp(u, corner(0, 1, 1), corner(1, 1, 1))),
+        )
+    }
+
+    /// Fractional Brownian motion: octaves of doubling frequency and halving
+    /// amplitude, normalized back to about [-1, 1].
+    pub fn fbm(&self, point: Point3d, octaves: u32) -> f32 {
+        self.octaves(point, octaves, |value| value)
+    }
+
+    /// Like `fbm` but of the absolute noise, in [0, 1] with sharp creases.
+    pub fn turbulence(&self, point: Point3d, octaves: u32) -> f32 {
+        self.octaves(point, octaves, f32::abs)
+    }
+
+    fn octaves(&self, point: Point3d, octaves: u32, shape: impl Fn(f32) -> f32) -> f32 {
+        let mut sum = 0.;
+        let mut total = 0.;
+        let mut amplitude = 1.;
+        let mut frequency = 1.;
+        for _ in 0..octaves.clamp(1, Perlin::MAX_OCTAVES) {
+            sum += amplitude * shape(self.noise(point * frequency));
+            total += amplitude;
+            amplitude *= 0.5;
+            frequency *= 2.;
+        }
+        sum / total
+    }
+}
+
+/// 6t^5 - 15t^4 + 10t^3, its first and second derivatives vanish at 0 and 1.
+fn fade(t: f32) -> f32 {
+    t * t * t * (t * (t * 6. - 15.) + 10.)
+}
+
+/// Dot product with one of the 12 edge directions of a cube picked by the hash.
+fn gradient(hash: i64, x: f32, y: f32, z: f32) -> f32 {
+    let h = hash & 15;
+    let u = if h < 8 { x } else { y };
+    let v = match h {
+        0..4 => y,
+        12 | 14 => x,
+        _ => z,
+    };
+    let u = if h & 1 == 0 { u } else { -u };
+    let v = if h & 2 == 0 { v } else { -v };
+    u + v
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn zero_on_the_lattice_and_bounded() {
+        let perlin = Perlin::new(7);
+        assert!(perlin.noise(Point3d::new(3., -2., 5.)) == 0.);
+
+        let mut rng = fastrand::Rng::with_seed(1);
+        for _ in 0..1000 {
+            let point = Point3d::new(rng.f32() * 20. - 10., rng.f32() * 20. - 10., rng.f32() * 20. - 10.);
+            assert!(perlin.noise(point).abs() <= 1.1);
+            assert!((0. ..=1.).contains(&perlin.turbulence(point, 4)));
+        }
+    }
+
+    #[test]
+    fn seeds_are_reproducible() {
+        let point = Point3d::new(0.3, 1.7, -2.2);
+        assert!(Perlin::new(3).fbm(point, 5) == Perlin::new(3).fbm(point, 5));
+        assert!(Perlin::new(3).noise(point) != Perlin::new(4).noise(point));
+    }
+}
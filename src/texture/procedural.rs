@@ -0,0 +1,141 @@
+use std::f32::consts::PI;
+
+use crate::primitives::vec3d::{Point3d, Vec3d};
+use crate::primitives::Color;
+
+use super::noise::Perlin;
+use super::Texture;
+
+/// Where a procedural texture is evaluated.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Mapping {
+    /// The hit point in world space, a solid texture.
+    Point,
+    /// The texture coordinates, as the point (u, v, 0).
+    Uv,
+}
+
+/// A scalar pattern in [0, 1] that blends the two colors of a texture.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Pattern {
+    /// Unit cubes alternating between the colors.
+    Checker,
+    /// Lines of the second color along every integer coordinate.
+    Grid { line_width: f32 },
+    /// Linear ramp from 0 at the origin to 1 at the tip of `axis`.
+    Gradient { axis: Vec3d },
+    /// Perlin noise, fBm of several octaves if `octaves` is above 1.
+    Noise { octaves: u32 },
+    /// Octaves of absolute noise.
+    Turbulence { octaves: u32 },
+    /// Bands along x distorted by turbulence.
+    Marble { octaves: u32, strength: f32 },
+    /// Rings around the z axis distorted by noise.
+    Wood { strength: f32 },
+}
+
+#[derive(Debug)]
+pub struct Procedural {
+    pub pattern: Pattern,
+    pub mapping: Mapping,
+    /// Frequency of the pattern, the coordinates are multiplied by it.
+    pub scale: f32,
+    pub primary: Color,
+    pub secondary: Color,
+    noise: Perlin,
+}
+
+impl Procedural {
+    /// A white and black pattern of the hit point with a unit scale.
+    pub fn new(pattern: Pattern) -> Procedural {
+        Procedural {
+            pattern,
+            mapping: Mapping::Point,
+            scale: 1.,
+            primary: Color::new(1., 1., 1.),
+            secondary: Color::new(0., 0., 0.),
+            noise: Perlin::new(0),
+        }
+    }
+
+    pub fn with_seed(mut self, seed: u64) -> Procedural {
+        self.noise = Perlin::new(seed);
+        self
+    }
+
+    /// The pattern at already scaled coordinates.
+    fn blend(&self, p: Point3d) -> f32 {
+        match self.pattern {
+            Pattern::Checker => {
+                let parity = p.x.floor() as i64 + p.y.floor() as i64 + p.z.floor() as i64;
+                (parity & 1) as f32
+            }
+            Pattern::Grid { line_width } => {
+                // texture coordinates have no third axis to draw lines along
+                let axes = match self.mapping {
+                    Mapping::Point => 3,
+                    Mapping::Uv => 2,
+                };
+                let on_line = (0..axes).any(|axis| {
+                    let offset = p[axis] - p[axis].round();
+                    offset.abs() < line_width / 2.
+                });
+                if on_line { 1. } else { 0. }
+            }
+            Pattern::Gradient { axis } => (p.dot(&axis) / axis.len_squared()).clamp(0., 1.),
+            Pattern::Noise { octaves } => 0.5 * (1. + self.noise.fbm(p, octaves)),
+            Pattern::Turbulence { octaves } => self.noise.turbulence(p, octaves),
+            Pattern::Marble { octaves, strength } => {
+                0.5 * (1. + f32::sin(PI * (p.x + strength * self.noise.turbulence(p, octaves))))
+            }
+            Pattern::Wood { strength } => {
+                let rings = f32::sqrt(p.x * p.x + p.y * p.y) + strength * self.noise.noise(p);
+                rings - rings.floor()
+            }
+        }
+    }
+}
+
+impl Texture for Procedural {
+    fn value(&self, (u, v): (f32, f32), point: Point3d) -> Color {
+        let p = match self.mapping {
+            Mapping::Point => point,
+            Mapping::Uv => Point3d::new(u, v, 0.),
+        };
+        let t = self.blend(p * self.scale);
+        self.primary * (1. - t) + self.secondary * t
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn red(texture: &Procedural, point: Point3d) -> f32 {
+        texture.value((0., 0.), point).red
+    }
+
+    #[test]
+    fn checker_alternates_in_every_axis() {
+        let mut texture = Procedural::new(Pattern::Checker);
+        texture.scale = 2.;
+
+        assert!(red(&texture, Point3d::new(0.1, 0.1, 0.1)) == 1.);
+        assert!(red(&texture, Point3d::new(0.6, 0.1, 0.1)) == 0.);
+        assert!(red(&texture, Point3d::new(0.1, 0.1, -0.1)) == 0.);
+        assert!(red(&texture, Point3d::new(-0.1, -0.1, 0.1)) == 1.);
+    }
+
+    #[test]
+    fn grid_and_gradient_of_texture_coordinates() {
+        let mut grid = Procedural::new(Pattern::Grid { line_width: 0.1 });
+        grid.mapping = Mapping::Uv;
+        assert!(grid.value((0.5, 0.5), Point3d::new(0., 0., 0.)).red == 1.);
+        assert!(grid.value((0.98, 0.5), Point3d::new(0., 0., 0.)).red == 0.);
+
+        let mut gradient = Procedural::new(Pattern::Gradient { axis: Vec3d::new(0., 2., 0.) });
+        gradient.mapping = Mapping::Uv;
+        assert!(gradient.value((0., 1.), Point3d::new(5., 5., 5.)).red == 0.5);
+        assert!(gradient.value((0., 3.), Point3d::new(5., 5., 5.)).red == 0.);
+    }
+}
@@ -0,0 +1,220 @@
+use std::fmt;
+
+use crate::primitives::Color;
+
+/// Operators compressing scene radiance into the displayable [0, 1] range.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum ToneMapping {
+    /// Cuts everything above 1.
+    Clamp,
+    Reinhard,
+    /// Reinhard that maps `white` and everything above it to 1.
+    ExtendedReinhard { white: f32 },
+    /// Stephen Hill's fit of the ACES reference and output transforms.
+    Aces,
+    /// Polynomial approximation of the AgX base look.
+    Agx,
+}
+
+/// The film development stage: exposure, tone mapping and the sRGB transfer curve.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ToneMapper {
+    pub operator: ToneMapping,
+    /// Exposure compensation in stops.
+    pub exposure: f32,
+}
+
+impl ToneMapping {
+    pub fn next(&self) -> ToneMapping {
+        match self {
+            ToneMapping::Clamp => ToneMapping::Reinhard,
+            ToneMapping::Reinhard => ToneMapping::ExtendedReinhard { white: 4. },
+            ToneMapping::ExtendedReinhard { .. } => ToneMapping::Aces,
+            ToneMapping::Aces => ToneMapping::Agx,
+            ToneMapping::Agx => ToneMapping::Clamp,
+        }
+    }
+
+    /// Maps linear radiance to linear display values.
+    pub fn apply(&self, color: Color) -> Color {
+        match *self {
+            ToneMapping::Clamp => color.map(|v| v.clamp(0., 1.)),
+            ToneMapping::Reinhard => scale_luminance(color, |l| l / (1. + l)),
+            ToneMapping::ExtendedReinhard { white } => {
+                scale_luminance(color, |l| f32::min(l * (1. + l / (white * white)) / (1. + l), 1.))
+            }
+            ToneMapping::Aces => aces(color),
+            ToneMapping::Agx => agx(color),
+        }
+    }
+}
+
+impl fmt::Display for ToneMapping {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            ToneMapping::Clamp => write!(f, "clamp"),
+            ToneMapping::Reinhard => write!(f, "Reinhard"),
+            ToneMapping::ExtendedReinhard { white } => write!(f, "extended Reinhard (white {white})"),
+            ToneMapping::Aces => write!(f, "ACES"),
+            ToneMapping::Agx => write!(f, "AgX"),
+        }
+    }
+}
+
+impl ToneMapper {
+    /// Linear radiance to sRGB encoded display values.
+    pub fn apply(&self, color: Color) -> Color {
+        let exposed = color * self.exposure.exp2();
+        let mapped = self.operator.apply(exposed);
+        mapped.map(|v| linear_to_srgb(v.clamp(0., 1.)))
+    }
+}
+
+impl Default for ToneMapper {
+    fn default() -> Self {
+        ToneMapper {
+            operator: ToneMapping::Aces,
+            exposure: 0.,
+        }
+    }
+}
+
+pub fn linear_to_srgb(value: f32) -> f32 {
+    if value <= 0.0031308 {
+        12.92 * value
+    } else {
+        1.055 * value.powf(1. / 2.4) - 0.055
+    }
+}
+
+pub fn srgb_to_linear(value: f32) -> f32 {
+    if value <= 0.04045 {
+        value / 12.92
+    } else {
+        ((value + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+fn scale_luminance(color: Color, curve: impl Fn(f32) -> f32) -> Color {
+    let luminance = color.luminance();
+    if luminance <= 0. {
+        return Color::new(0., 0., 0.)
+    }
+    color * (curve(luminance) / luminance)
+}
+
+fn mul(m: &[[f32; 3]; 3], c: Color) -> Color {
+    Color::new(
+        m[0][0] * c.red + m[0][1] * c.green + m[0][2] * c.blue,
+        m[1][0] * c.red + m[1][1] * c.green + m[1][2] * c.blue,
+        m[2][0] * c.red + m[2][1] * c.green + m[2][2] * c.blue,
+    )
+}
+
+fn aces(color: Color) -> Color {
+    // sRGB => XYZ => D65_2_D60 => AP1 => RRT_SAT
+    const INPUT: [[f32; 3]; 3] = [
+        [0.59719, 0.35458, 0.04823],
+        [0.07600, 0.90834, 0.01566],
+        [0.02840, 0.13383, 0.83777],
+    ];
+    // ODT_SAT => XYZ => D60_2_D65 => sRGB
+    const OUTPUT: [[f32; 3]; 3] = [
+        [1.60475, -0.53108, -0.07367],
+        [-0.10208, 1.10813, -0.00605],
+        [-0.00327, -0.07276, 1.07602],
+    ];
+    let v = mul(&INPUT, color);
+    let v = v.map(|x| (x * (x + 0.0245786) - 0.000090537) / (x * (0.983729 * x + 0.432951) + 0.238081));
+    mul(&OUTPUT, v).map(|x| x.clamp(0., 1.))
+}
+
+fn agx(color: Color) -> Color {
+    const INSET: [[f32; 3]; 3] = [
+        [0.842_479, 0.078_434, 0.079_224],
+        [0.042_328, 0.878_469, 0.079_166],
+        [0.042_376, 0.078_434, 0.879_143],
+    ];
+    const OUTSET: [[f32; 3]; 3] = [
+        [1.196_879, -0.098_021, -0.099_030],
+        [-0.052_897, 1.151_903, -0.098_961],
+        [-0.052_972, -0.098_043, 1.151_074],
+    ];
+    const MIN_EV: f32 = -12.47393;
+    const MAX_EV: f32 = 4.026069;
+
+    let v = mul(&INSET, color).map(|x| {
+        let ev = x.max(1e-10).log2().clamp(MIN_EV, MAX_EV);
+        let x = (ev - MIN_EV) / (MAX_EV - MIN_EV);
+        // sigmoid contrast curve fitted with a polynomial
+        let x2 = x * x;
+        let x4 = x2 * x2;
+        15.5 * x4 * x2 - 40.14 * x4 * x + 31.96 * x4 - 6.868 * x2 * x + 0.4298 * x2 + 0.1191 * x - 0.00232
+    });
+    // the curve yields display encoded values, bring them back to linear
+    mul(&OUTSET, v).map(|x| x.max(0.).powf(2.2).clamp(0., 1.))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operators() -> [ToneMapping; 5] {
+        [
+            ToneMapping::Clamp,
+            ToneMapping::Reinhard,
+            ToneMapping::ExtendedReinhard { white: 4. },
+            ToneMapping::Aces,
+            ToneMapping::Agx,
+        ]
+    }
+
+    #[test]
+    fn black_stays_black() {
+        for operator in operators() {
+            let mapped = operator.apply(Color::new(0., 0., 0.));
+            assert!(mapped.luminance() < 1e-3, "{operator}");
+        }
+    }
+
+    #[test]
+    fn output_is_displayable() {
+        for operator in operators() {
+            for value in [0.01, 0.5, 1., 10., 1000.] {
+                let mapped = operator.apply(Color::new(value, value, value));
+                assert!((0. ..=1.).contains(&mapped.luminance()), "{operator} {value}");
+            }
+        }
+    }
+
+    #[test]
+    fn operators_are_monotonic() {
+        for operator in operators() {
+            let mut previous = -1.;
+            for value in [0.01, 0.1, 0.5, 1., 2., 3.9] {
+                let luminance = operator.apply(Color::new(value, value, value)).luminance();
+                assert!(luminance >= previous, "{operator} {value}");
+                previous = luminance;
+            }
+        }
+    }
+
+    #[test]
+    fn extended_reinhard_maps_white_to_one() {
+        let mapped = ToneMapping::ExtendedReinhard { white: 4. }.apply(Color::new(4., 4., 4.));
+        assert!((mapped.luminance() - 1.).abs() < 1e-5);
+    }
+
+    #[test]
+    fn srgb_curve_endpoints() {
+        assert!(linear_to_srgb(0.) == 0.);
+        assert!((linear_to_srgb(1.) - 1.).abs() < 1e-6);
+    }
+
+    #[test]
+    fn srgb_curve_roundtrip() {
+        for value in [0.001, 0.02, 0.2, 0.5, 0.9] {
+            assert!((srgb_to_linear(linear_to_srgb(value)) - value).abs() < 1e-5);
+        }
+    }
+}